@@ -0,0 +1,1096 @@
+use traits;
+use element_array::ElementArray;
+use rng::SeededRng;
+use rules::{GameMode, GameRules};
+use element::{ElementType, ElementTypeList};
+use replay::{Replay, ReplayAction, ReplayStep};
+use save::SaveGame;
+use puzzle::{Puzzle, PuzzleGoal};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GridSize
+{
+    FOUR  = 4,
+    FIVE  = 5,
+    SIX   = 6,
+    SEVEN = 7,
+    EIGHT = 8,
+}
+impl GridSize
+{
+    pub fn from_value(value : i32) -> Option<GridSize>
+    {
+        match value
+        {
+            4 => Some(GridSize::FOUR),
+            5 => Some(GridSize::FIVE),
+            6 => Some(GridSize::SIX),
+            7 => Some(GridSize::SEVEN),
+            8 => Some(GridSize::EIGHT),
+            _ => None,
+        }
+    }
+}
+
+#[derive(PartialEq)]
+pub enum GameState
+{
+    PLAY,
+    FALLING,
+    REACTING,
+    READYING,
+    GAME_OVER,
+    EDITING, //Laid out for the puzzle editor, nothing is played
+}
+
+//Game rules and flow of a single board, independent of any renderer
+pub struct Board
+{
+    game_state : GameState,
+    time : f32,
+
+    grid_size : GridSize,
+
+    elements : ElementArray,
+
+    //Inputs of the current game
+    recording : Replay,
+    //Replay being played back, with its next step
+    playback : Option<(Replay, usize)>,
+    next_rules : Option<GameRules>, //Replaces the rules when the next game starts
+    //When the pair in control drops by itself, and how long it was given, in timed games
+    drop_timer : Option<(f32, f32)>,
+    //Puzzle being played, and whether its goal was met
+    puzzle : Option<Puzzle>,
+    puzzle_solved : bool,
+}
+
+impl Board
+{
+    pub fn new() -> Board
+    {
+        let size = GridSize::SIX;
+        Board
+        {
+            game_state : GameState::PLAY,
+            time : 0.0,
+
+            grid_size : size,
+
+            elements : ElementArray::new(&size),
+
+            recording : Replay::new(0, size, &GameRules::new(), 0),
+            playback : None,
+            next_rules : None,
+            drop_timer : None,
+            puzzle : None,
+            puzzle_solved : false,
+        }
+    }
+
+    pub fn upscale(&mut self)
+    {
+        let size = match self.grid_size
+        {
+            GridSize::FOUR  => GridSize::FIVE,
+            GridSize::FIVE  => GridSize::SIX,
+            GridSize::SIX   => GridSize::SEVEN,
+            GridSize::SEVEN => GridSize::EIGHT,
+            GridSize::EIGHT => GridSize::EIGHT,
+        };
+        self.reset(size)
+    }
+    pub fn downscale(&mut self)
+    {
+        let size = match self.grid_size
+        {
+            GridSize::FOUR  => GridSize::FOUR,
+            GridSize::FIVE  => GridSize::FOUR,
+            GridSize::SIX   => GridSize::FIVE,
+            GridSize::SEVEN => GridSize::SIX,
+            GridSize::EIGHT => GridSize::SEVEN,
+        };
+        self.reset(size)
+    }
+
+    //New game with a fresh seed
+    pub fn reset(&mut self, size: GridSize)
+    {
+        self.reset_with_seed(size, SeededRng::random_seed());
+    }
+    //New game replaying the element sequence of a given seed
+    pub fn reset_with_seed(&mut self, size: GridSize, seed: u64)
+    {
+        if let Some(rules) = self.next_rules.take()
+        {
+            self.elements.rules = rules;
+        }
+        self.start_game(size, seed);
+    }
+    //New game with the rules as they are
+    fn start_game(&mut self, size: GridSize, seed: u64)
+    {
+        //Set new size
+        self.grid_size = size;
+        self.time = 0.0;
+        self.recording = Replay::new(seed, size, &self.elements.rules, self.elements.element_data.get_hash());
+        self.playback = None;
+        self.puzzle = None;
+
+        //Empties grid & resizes it
+        self.elements.reset(&size, seed);
+        //Get Elements to play, too
+        self.elements.get_next_pair(false);
+
+        //Reset to play state
+        self.game_state = GameState::PLAY;
+        self.start_drop_timer();
+    }
+    //Counts down from now for the pair in control, at the speed of the elements made so far
+    fn start_drop_timer(&mut self)
+    {
+        let level = self.elements.get_speed_level();
+        self.drop_timer = self.elements.rules.get_drop_time(level).map(|length| (self.time + length, length));
+    }
+
+    //Starts a puzzle over, with the puzzle rules; the rules from before come back with the next game
+    pub fn start_puzzle(&mut self, puzzle: &Puzzle)
+    {
+        self.load_puzzle(puzzle);
+        self.game_state = GameState::PLAY;
+        self.start_drop_timer();
+    }
+    //Shows a puzzle as it starts, without playing it
+    pub fn edit_puzzle(&mut self, puzzle: &Puzzle)
+    {
+        self.load_puzzle(puzzle);
+        self.game_state = GameState::EDITING;
+        self.drop_timer = None;
+    }
+    fn load_puzzle(&mut self, puzzle: &Puzzle)
+    {
+        if self.next_rules.is_none()
+        {
+            self.next_rules = Some(self.elements.rules.clone());
+        }
+        self.elements.rules = GameRules::for_mode(GameMode::PUZZLE);
+        self.grid_size = puzzle.grid_size;
+        self.time = 0.0;
+        self.recording = Replay::new(0, puzzle.grid_size, &self.elements.rules, self.elements.element_data.get_hash());
+        self.playback = None;
+        self.elements.load_puzzle(puzzle);
+        self.puzzle = Some(puzzle.clone());
+        self.puzzle_solved = false;
+    }
+    pub fn get_puzzle(&self) -> Option<&Puzzle>
+    {
+        self.puzzle.as_ref()
+    }
+    //Once the puzzle is over; a full grid fails it like running out of drops
+    pub fn is_puzzle_solved(&self) -> bool
+    {
+        self.puzzle_solved
+    }
+    //A reaction just happened
+    fn check_puzzle_made(&mut self)
+    {
+        if let Some(PuzzleGoal::MAKE(t)) = self.puzzle.as_ref().map(|p| p.goal)
+        {
+            self.puzzle_solved = self.puzzle_solved || self.elements.has_made(&t);
+        }
+    }
+    //A drop just settled: whether the puzzle is over, solved or out of drops
+    fn settle_puzzle(&mut self) -> bool
+    {
+        let (goal, drops) = match self.puzzle
+        {
+            Some(ref puzzle) => (puzzle.goal, puzzle.drops),
+            None => return false,
+        };
+        self.puzzle_solved = self.puzzle_solved || match goal
+        {
+            PuzzleGoal::MAKE(_) => false, //Checked as reactions happen
+            PuzzleGoal::CLEAR(t) => self.elements.count_elements(&t) == 0,
+            PuzzleGoal::SCORE(points) => self.get_score() >= points,
+        };
+        self.puzzle_solved || self.get_pairs_dropped() >= drops
+    }
+
+    //Nothing to save once the game is over, or for a replay or a puzzle
+    pub fn save_game(&self) -> Option<SaveGame>
+    {
+        let settling = match self.game_state
+        {
+            GameState::PLAY => false,
+            GameState::GAME_OVER => return None,
+            _ => true,
+        };
+        if self.playback.is_some() || self.puzzle.is_some()
+        {
+            return None
+        }
+        let mut save = self.elements.save_state();
+        save.time = self.time;
+        save.settling = settling;
+        save.drop_time_left = self.drop_timer.map(|(deadline, _)| (deadline - self.time).max(0.0));
+        save.steps = self.recording.steps.clone();
+        Some(save)
+    }
+    pub fn resume(&mut self, save: &SaveGame) -> Result<(), String>
+    {
+        self.elements.load_state(save)?;
+        self.grid_size = save.grid_size;
+        self.time = save.time;
+        self.recording = Replay::new(save.seed, save.grid_size, &self.elements.rules, self.elements.element_data.get_hash());
+        self.recording.steps = save.steps.clone();
+        self.playback = None;
+        self.puzzle = None;
+        //Falling elements settle again from their tiles, then play continues with the next pair
+        self.game_state = if save.settling { GameState::FALLING } else { GameState::PLAY };
+        self.start_drop_timer();
+        if let (Some(timer), Some(left)) = (self.drop_timer.as_mut(), save.drop_time_left)
+        {
+            //Picks up where it was, rather than starting over
+            timer.0 = self.time + left;
+        }
+        Ok(())
+    }
+
+    //Starts the game of a replay over with its rules, then plays its inputs back as the game goes
+    //The rules from before come back with the next game, as after a puzzle
+    pub fn play_replay(&mut self, replay: Replay) -> Result<(), String>
+    {
+        if replay.element_hash != self.elements.element_data.get_hash()
+        {
+            return Err("Replay was recorded with other element definitions".to_owned())
+        }
+        if self.next_rules.is_none()
+        {
+            self.next_rules = Some(self.elements.rules.clone());
+        }
+        self.elements.rules = replay.rules.clone();
+        self.start_game(replay.grid_size, replay.seed);
+        self.playback = Some((replay, 0));
+        Ok(())
+    }
+    pub fn is_replaying(&self) -> bool
+    {
+        self.playback.is_some()
+    }
+    //Inputs of the current game so far
+    pub fn get_replay(&self) -> &Replay
+    {
+        &self.recording
+    }
+
+    pub fn get_grid_size(&self) -> GridSize
+    {
+        self.grid_size
+    }
+    pub fn get_seed(&self) -> u64
+    {
+        self.elements.get_seed()
+    }
+    pub fn get_score(&self) -> u64
+    {
+        self.elements.get_score()
+    }
+    pub fn get_chain(&self) -> u32
+    {
+        self.elements.get_chain()
+    }
+    pub fn get_longest_chain(&self) -> u32
+    {
+        self.elements.get_longest_chain()
+    }
+    pub fn get_pairs_dropped(&self) -> u32
+    {
+        self.elements.get_pairs_dropped()
+    }
+    pub fn get_garbage_received(&self) -> u32
+    {
+        self.elements.get_garbage_received()
+    }
+    pub fn get_garbage_cleared(&self) -> u32
+    {
+        self.elements.get_garbage_cleared()
+    }
+    pub fn get_garbage_countdown(&self) -> Option<u32>
+    {
+        self.elements.get_garbage_countdown()
+    }
+    //Falls in once the current drop settles, e.g. sent by an opponent
+    pub fn add_garbage(&mut self, count: u32)
+    {
+        if !self.is_game_over()
+        {
+            self.elements.add_garbage(count);
+        }
+    }
+    //Whether garbage is earned for an opponent, from now on
+    pub fn set_opponent(&mut self, has_opponent: bool)
+    {
+        self.elements.set_opponent(has_opponent);
+    }
+    //Garbage earned by large groups and chains since last asked, to send to an opponent
+    pub fn take_garbage_outgoing(&mut self) -> u32
+    {
+        self.elements.take_garbage_outgoing()
+    }
+    pub fn get_garbage_sent(&self) -> u32
+    {
+        self.elements.get_garbage_sent()
+    }
+    pub fn get_highest_element(&self) -> Option<ElementType>
+    {
+        self.elements.get_highest_element()
+    }
+    pub fn get_speed_level(&self) -> u32
+    {
+        self.elements.get_speed_level()
+    }
+    //Seconds left before the pair in control drops by itself, out of the time it was given
+    //Nothing unless the game is timed and the pair can still move, or while a replay plays back
+    pub fn get_drop_countdown(&self) -> Option<(f32, f32)>
+    {
+        match (&self.game_state, self.drop_timer, &self.playback)
+        {
+            (&GameState::PLAY, Some((deadline, length)), &None) => Some(((deadline - self.time).max(0.0), length)),
+            _ => None,
+        }
+    }
+    //Seconds left in a mode played against the clock
+    pub fn get_time_left(&self) -> Option<f32>
+    {
+        self.elements.rules.mode.get_time_limit().map(|limit| (limit - self.time).max(0.0))
+    }
+    //The game ended because its time was up, rather than with a full grid
+    pub fn is_out_of_time(&self) -> bool
+    {
+        self.is_game_over() && self.get_time_left() == Some(0.0)
+    }
+    pub fn is_game_over(&self) -> bool
+    {
+        match self.game_state
+        {
+            GameState::GAME_OVER => true,
+            _ => false,
+        }
+    }
+    pub fn get_rules(&self) -> &GameRules
+    {
+        &self.elements.rules
+    }
+    //Takes effect from the next game
+    pub fn set_element_data(&mut self, data: ElementTypeList)
+    {
+        self.elements.element_data = data;
+    }
+    //Applies to the current game and the ones after it
+    pub fn set_rules(&mut self, rules: GameRules)
+    {
+        self.elements.rules = rules;
+    }
+    //Takes effect from the next game, so recordings keep the rules they started with
+    pub fn set_next_rules(&mut self, rules: GameRules)
+    {
+        self.next_rules = Some(rules);
+    }
+    //Rules the next game will start with
+    pub fn get_next_rules(&self) -> &GameRules
+    {
+        self.next_rules.as_ref().unwrap_or(&self.elements.rules)
+    }
+    pub fn get_state(&self) -> &GameState
+    {
+        &self.game_state
+    }
+    pub fn get_elements(&self) -> &ElementArray
+    {
+        &self.elements
+    }
+
+    //INPUTS
+    //Player inputs are ignored while a replay plays back
+    pub fn drop_pair(&mut self)
+    {
+        if self.playback.is_none() { self.apply_action(ReplayAction::DROP); }
+    }
+    pub fn rotate_pair(&mut self)
+    {
+        if self.playback.is_none() { self.apply_action(ReplayAction::ROTATE); }
+    }
+    pub fn move_pair(&mut self, dx : i32)
+    {
+        if self.playback.is_none() { self.apply_action(ReplayAction::MOVE(dx)); }
+    }
+    pub fn hold_pair(&mut self)
+    {
+        if self.playback.is_none() { self.apply_action(ReplayAction::HOLD); }
+    }
+    //Returns whether the action took effect, only those are recorded
+    fn apply_action(&mut self, action: ReplayAction) -> bool
+    {
+        let applied = match (&self.game_state, action)
+        {
+            (&GameState::PLAY, ReplayAction::DROP) =>
+            {
+                self.game_state = GameState::FALLING;
+                self.drop_timer = None;
+                self.elements.drop_pair();
+                self.elements.make_fall();
+                true
+            },
+            (&GameState::PLAY, ReplayAction::ROTATE) =>
+            {
+                self.elements.rotate_pair();
+                true
+            },
+            (&GameState::PLAY, ReplayAction::HOLD) => self.elements.hold_pair(),
+            (&GameState::EDITING, _) => false,
+            (&GameState::GAME_OVER, ReplayAction::MOVE(_)) => false,
+            (_, ReplayAction::MOVE(dx)) =>
+            {
+                self.elements.move_pair(dx);
+                true
+            },
+            _ => false,
+        };
+        if applied
+        {
+            self.recording.steps.push(ReplayStep { time : self.time, action : action });
+        }
+        applied
+    }
+    //Apply the replay steps that are due, in order, each waiting until the board accepts it
+    fn play_back(&mut self)
+    {
+        loop
+        {
+            let step = match self.playback
+            {
+                Some((ref replay, next)) if next < replay.steps.len() => replay.steps[next],
+                _ => return,
+            };
+            if step.time > self.time || !self.apply_action(step.action)
+            {
+                return
+            }
+            if let Some((_, ref mut next)) = self.playback
+            {
+                *next += 1;
+            }
+        }
+    }
+    //Ends the game where it stands, e.g. once the opponent has lost
+    pub fn stop(&mut self)
+    {
+        self.game_state = GameState::GAME_OVER;
+        self.drop_timer = None;
+    }
+    //Only once the game is over, so the results stay up until asked
+    pub fn restart(&mut self)
+    {
+        match self.game_state
+        {
+            GameState::GAME_OVER =>
+            {
+                //Puzzles are tried again
+                match self.puzzle.take()
+                {
+                    Some(puzzle) => self.start_puzzle(&puzzle),
+                    None =>
+                    {
+                        let size = self.grid_size;
+                        self.reset(size);
+                    },
+                }
+            },
+            _ => (),
+        }
+    }
+}
+
+impl traits::Updatable for Board
+{
+    fn update(&mut self, delta_t : f32)
+    {
+        self.play_back();
+        //Whatever is still falling when time is up does not count
+        if self.get_time_left() == Some(0.0)
+        {
+            self.game_state = GameState::GAME_OVER;
+        }
+        match self.game_state
+        {
+            GameState::PLAY => //WAITING FOR FALL INPUT
+            {
+                //Deal with inputs directly
+                //We only need to update positions here
+                self.elements.move_elements(delta_t);
+                //Out of time, the pair drops where it is
+                //Replays have those drops among their steps already
+                match self.drop_timer
+                {
+                    Some((deadline, _)) if self.time >= deadline && self.playback.is_none() =>
+                    {
+                        self.apply_action(ReplayAction::DROP);
+                    },
+                    _ => (),
+                }
+            },
+            GameState::FALLING => //WAITING FOR ELEMENTS TO SETTLE
+            {
+                if self.elements.move_elements(delta_t)
+                {
+                    if self.elements.test_reactions()
+                    {
+                        self.check_puzzle_made();
+                        self.game_state = GameState::REACTING;
+                    }
+                    else if self.elements.drop_garbage()
+                    {
+                        //Garbage falls in before the board is checked
+                    }
+                    else if self.settle_puzzle()
+                    {
+                        //Board stays as it is, solved or not
+                        self.game_state = GameState::GAME_OVER;
+                    }
+                    else if self.elements.test_above()
+                    {
+                        //Continue playing
+                        self.elements.set_next_position(true); //Move next pair away
+                        self.game_state = GameState::READYING;
+                    }
+                    else
+                    {
+                        //Board stays frozen until restarted
+                        self.game_state = GameState::GAME_OVER;
+                    }
+                }
+            },
+            GameState::REACTING => //WAITING FOR ELEMENTS TO STOP REACTING
+            {
+                self.elements.make_fall();
+                self.game_state = GameState::FALLING;
+            },
+            GameState::READYING => //WAITING FOR NEXT PAIR ANIMATION
+            {
+                if self.elements.move_elements(delta_t)
+                {
+                    self.elements.set_next_position(false);
+                    self.elements.get_next_pair(true);
+                    self.game_state = GameState::PLAY;
+                    self.start_drop_timer();
+                }
+            },
+            GameState::GAME_OVER => (), //WAITING FOR RESTART INPUT
+            GameState::EDITING => //WAITING FOR THE EDITOR TO START THE PUZZLE
+            {
+                //Pairs still come into place
+                self.elements.move_elements(delta_t);
+            },
+        }
+        //The clock stops with the game
+        if !self.is_game_over()
+        {
+            self.time += delta_t;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use traits::Updatable;
+    use rules::{GameMode, ProductPlacement};
+
+    //Runs the board until the pair in control can be played again, or the game is over
+    fn settle(board : &mut Board)
+    {
+        for _ in 0..10_000
+        {
+            board.update(0.05);
+            if *board.get_state() == GameState::PLAY || board.is_game_over()
+            {
+                return
+            }
+        }
+        panic!("board never settled");
+    }
+    //Same inputs every game: spread the drops over the columns, rotating now and then
+    fn play(board : &mut Board, drops : usize)
+    {
+        for i in 0..drops
+        {
+            if board.is_game_over()
+            {
+                return
+            }
+            if i % 3 == 0 { board.rotate_pair(); }
+            board.move_pair((i % 5) as i32 - 2);
+            board.drop_pair();
+            settle(board);
+        }
+    }
+
+    //Puzzle made up for a test, the board as rows from the top like in content/puzzles.txt
+    fn puzzle(size : i32, rows : &str, pairs : &str) -> Puzzle
+    {
+        let text = format!("[test]\nsize = {}\nboard = {}\npairs = {}\ngoal = score 1000\n", size, rows, pairs);
+        Puzzle::parse_pack(&text, "test", &ElementTypeList::new()).unwrap().remove(0)
+    }
+    //Rows of the grid from the top, in the same format
+    fn rows(board : &Board) -> Vec<String>
+    {
+        let data = ElementTypeList::new();
+        let width = board.get_grid_size() as i32;
+        let elements = board.get_elements();
+        let name_at = |px, py| elements.get_elements().iter()
+            .find(|e| { let (x, y) = e.get_pos(); elements.get_tile_at(x, y) == Some((px, py)) })
+            .map(|e| data.get_name(e.get_type()).to_owned()).unwrap_or("-".to_owned());
+        (0..width).rev().map(|py| (0..width).map(|px| name_at(px, py)).collect::<Vec<String>>().join(" ")).collect()
+    }
+
+    #[test]
+    fn new_game_deals_a_pair_and_its_queue()
+    {
+        let mut board = Board::new();
+        board.reset_with_seed(GridSize::SIX, 7);
+        assert!(*board.get_state() == GameState::PLAY);
+        assert_eq!(board.get_seed(), 7);
+        assert_eq!(board.get_elements().get_elements().len(), 2);
+        assert_eq!(board.get_elements().get_next_elements().len(), 2);
+        assert_eq!(board.get_elements().get_queue_elements().len(), 2 * (board.get_rules().next_pairs - 1));
+    }
+
+    #[test]
+    fn no_next_pairs_still_deals_and_holds()
+    {
+        let mut rules = GameRules::new();
+        rules.next_pairs = 0;
+        let mut board = Board::new();
+        board.set_rules(rules);
+        board.reset_with_seed(GridSize::SIX, 21);
+        assert!(board.get_elements().get_next_elements().is_empty());
+        assert!(board.get_elements().get_queue_elements().is_empty());
+
+        //Nothing held yet: the held pair is replaced by a fresh one
+        let first = board.save_game().unwrap().pair;
+        board.hold_pair();
+        let save = board.save_game().unwrap();
+        assert_eq!(save.hold, first);
+        assert!(save.pair[0].is_some() && save.pair[1].is_some());
+        assert!(board.get_elements().get_next_elements().is_empty());
+
+        play(&mut board, 3);
+        assert_eq!(board.get_pairs_dropped(), 3);
+        assert!(board.get_elements().get_next_elements().is_empty());
+        //Held pair comes back in place of the one in control
+        let second = board.save_game().unwrap().pair;
+        board.hold_pair();
+        let save = board.save_game().unwrap();
+        assert_eq!(save.pair, first);
+        assert_eq!(save.hold, second);
+    }
+
+    #[test]
+    fn a_drop_settles_back_to_play()
+    {
+        let mut board = Board::new();
+        board.reset_with_seed(GridSize::SIX, 7);
+        board.drop_pair();
+        assert!(*board.get_state() == GameState::FALLING);
+        settle(&mut board);
+        assert_eq!(board.get_pairs_dropped(), 1);
+        assert!(*board.get_state() == GameState::PLAY);
+    }
+
+    #[test]
+    fn same_seed_and_inputs_play_the_same_game()
+    {
+        let mut first = Board::new();
+        let mut second = Board::new();
+        first.reset_with_seed(GridSize::FIVE, 1234);
+        second.reset_with_seed(GridSize::FIVE, 1234);
+        play(&mut first, 40);
+        play(&mut second, 40);
+        assert_eq!(first.get_score(), second.get_score());
+        assert_eq!(first.get_pairs_dropped(), second.get_pairs_dropped());
+        assert_eq!(first.get_longest_chain(), second.get_longest_chain());
+    }
+
+    #[test]
+    fn replay_plays_the_game_out_again()
+    {
+        let mut board = Board::new();
+        board.reset_with_seed(GridSize::SIX, 99);
+        play(&mut board, 60);
+        let (score, pairs) = (board.get_score(), board.get_pairs_dropped());
+        assert!(pairs > 0);
+
+        //Through the file, as the game would
+        let path = ::std::env::temp_dir().join("metalchemist-test.replay");
+        board.get_replay().save(&path).unwrap();
+        let replay = Replay::load(&path).unwrap();
+        let _ = ::std::fs::remove_file(&path);
+
+        let mut watched = Board::new();
+        watched.play_replay(replay).unwrap();
+        for _ in 0..100_000
+        {
+            if watched.get_pairs_dropped() == pairs && *watched.get_state() == GameState::PLAY || watched.is_game_over()
+            {
+                break
+            }
+            watched.update(0.05);
+        }
+        assert_eq!(watched.get_pairs_dropped(), pairs);
+        assert_eq!(watched.get_score(), score);
+    }
+
+    //Chain fixture: the waters make a salt, which makes sulfur with the salts already there
+    fn chain_board(opponent : bool) -> Board
+    {
+        let mut board = Board::new();
+        board.set_opponent(opponent);
+        board.start_puzzle(&puzzle(4, "salt salt water water", "water fire, air air"));
+        board.move_pair(1);
+        board
+    }
+
+    #[test]
+    fn garbage_is_only_earned_against_an_opponent()
+    {
+        for &opponent in &[true, false]
+        {
+            let mut board = chain_board(opponent);
+            board.drop_pair();
+            settle(&mut board);
+            assert_eq!(board.get_longest_chain(), 2);
+            //Groups of three: only the second link earns one
+            assert_eq!(board.take_garbage_outgoing(), if opponent { 1 } else { 0 });
+        }
+    }
+
+    #[test]
+    fn large_groups_earn_garbage_and_cancel_what_is_coming()
+    {
+        //Four air: one past three
+        let drop = |pending : u32|
+        {
+            let mut board = Board::new();
+            board.set_opponent(true);
+            board.start_puzzle(&puzzle(4, "air - - - / air air - -", "air fire, water water"));
+            board.add_garbage(pending);
+            board.drop_pair();
+            settle(&mut board);
+            (board.take_garbage_outgoing(), board.elements.save_state().garbage_pending, board.get_elements().get_garbage_received())
+        };
+        assert_eq!(drop(0), (1, 0, 0));
+        assert_eq!(drop(1), (0, 0, 0));
+        assert_eq!(drop(3), (0, 0, 2));
+    }
+
+    #[test]
+    fn misses_bring_garbage_in()
+    {
+        let mut board = Board::new();
+        board.start_puzzle(&puzzle(4, "- - - -", "fire earth, water air, fire earth"));
+        let mut rules = GameRules::for_mode(GameMode::PUZZLE);
+        rules.garbage_after = 2;
+        rules.garbage_amount = 3;
+        board.set_rules(rules);
+        board.drop_pair();
+        settle(&mut board);
+        assert_eq!(board.get_elements().get_garbage_received(), 0);
+        assert_eq!(board.get_elements().get_garbage_countdown(), Some(1));
+        board.drop_pair();
+        settle(&mut board);
+        assert_eq!(board.get_elements().get_garbage_received(), 3);
+        assert_eq!(board.get_elements().count_elements(&ElementTypeList::new().get_garbage().unwrap()), 3);
+    }
+
+    #[test]
+    fn garbage_without_room_stays_pending()
+    {
+        //Three full rows that never react, the pair on top: room for ten more
+        let mut board = Board::new();
+        board.start_puzzle(&puzzle(4, "air water air water / water air water air / air water air water", "fire earth"));
+        board.add_garbage(12);
+        board.drop_pair();
+        settle(&mut board);
+        assert!(board.is_game_over());
+        assert_eq!(board.get_elements().get_garbage_received(), 10);
+        assert_eq!(board.elements.save_state().garbage_pending, 2);
+    }
+
+    #[test]
+    fn reactions_clear_the_garbage_next_to_them()
+    {
+        let mut board = Board::new();
+        board.start_puzzle(&puzzle(4, "- - - ash / ash earth earth ash", "earth water"));
+        board.drop_pair();
+        settle(&mut board);
+        //The ash on top touched none of the earths, it falls in place of the one below
+        assert_eq!(board.get_elements().get_garbage_cleared(), 2);
+        assert_eq!(rows(&board).pop().unwrap(), "- salt water ash");
+    }
+
+    #[test]
+    fn preview_shows_each_link_of_the_chain()
+    {
+        let mut board = chain_board(false);
+        let preview = board.get_elements().preview_drop().unwrap();
+        let tiles = |link : &Vec<(f32, f32)>|
+        {
+            let mut tiles : Vec<(i32, i32)> = link.iter().map(|&(x, y)| board.get_elements().get_tile_at(x, y).unwrap()).collect();
+            tiles.sort();
+            tiles
+        };
+        //The waters with the one dropped, then the salts with the one they made
+        assert_eq!(preview.links.iter().map(&tiles).collect::<Vec<Vec<(i32, i32)>>>(),
+                   vec![vec![(2, 0), (2, 1), (3, 0)], vec![(0, 0), (1, 0), (2, 0)]]);
+        assert_eq!(preview.chain, 2);
+        //Three salts, then three sulfurs at twice the points
+        assert_eq!(preview.score, 3 * 3 + 2 * 3 * 9);
+        board.drop_pair();
+        settle(&mut board);
+        assert_eq!(board.get_longest_chain(), preview.chain);
+        assert_eq!(board.get_score(), preview.score);
+    }
+
+    #[test]
+    fn replay_brings_its_rules_then_gives_them_back()
+    {
+        let mut board = Board::new();
+        board.set_rules(GameRules::for_mode(GameMode::TIME_ATTACK));
+        board.reset_with_seed(GridSize::SIX, 5);
+        play(&mut board, 5);
+
+        let mut watched = Board::new();
+        let mut mine = GameRules::new();
+        mine.set_timed(true);
+        watched.set_rules(mine);
+        watched.reset_with_seed(GridSize::FIVE, 1);
+        watched.play_replay(board.get_replay().clone()).unwrap();
+        assert!(watched.get_rules().mode == GameMode::TIME_ATTACK);
+        assert!(!watched.get_rules().is_timed());
+        watched.reset(GridSize::FIVE);
+        assert!(watched.get_rules().mode == GameMode::CLASSIC);
+        assert!(watched.get_rules().is_timed());
+    }
+
+    #[test]
+    fn replay_needs_the_same_elements()
+    {
+        let mut board = Board::new();
+        board.reset_with_seed(GridSize::SIX, 5);
+        let mut replay = Replay::new(5, GridSize::SIX, board.get_rules(), board.get_elements().element_data.get_hash());
+        replay.element_hash ^= 1;
+        assert!(board.play_replay(replay).is_err());
+        assert!(!board.is_replaying());
+    }
+
+    #[test]
+    fn resume_picks_up_the_game_and_its_rules()
+    {
+        let mut rules = GameRules::new();
+        rules.combo_multipliers = vec![1, 10];
+        rules.garbage_after = 7;
+        rules.garbage_amount = 2;
+        let mut board = Board::new();
+        board.set_rules(rules);
+        board.reset_with_seed(GridSize::SIX, 11);
+        play(&mut board, 8);
+
+        let path = ::std::env::temp_dir().join("metalchemist-test.save");
+        board.save_game().unwrap().save(&path).unwrap();
+        let save = SaveGame::load(&path);
+        let _ = ::std::fs::remove_file(&path);
+        let save = save.unwrap();
+
+        let mut resumed = Board::new();
+        resumed.resume(&save).unwrap();
+        assert_eq!(resumed.get_score(), board.get_score());
+        assert_eq!(resumed.get_rules().combo_multipliers, vec![1, 10]);
+        assert_eq!(resumed.get_rules().garbage_after, 7);
+        assert_eq!(resumed.get_rules().garbage_amount, 2);
+        //Both go on the same way
+        play(&mut board, 10);
+        play(&mut resumed, 10);
+        assert_eq!(resumed.get_score(), board.get_score());
+        assert_eq!(resumed.get_pairs_dropped(), board.get_pairs_dropped());
+    }
+
+    #[test]
+    fn resume_needs_something_to_spawn()
+    {
+        let mut board = Board::new();
+        board.reset_with_seed(GridSize::SIX, 11);
+        let mut save = board.save_game().unwrap();
+        save.unlocks = vec![];
+        assert!(Board::new().resume(&save).is_err());
+        save.unlocks = vec!["gold".to_owned()];
+        assert!(Board::new().resume(&save).is_err());
+    }
+
+    #[test]
+    fn resume_shows_no_more_pairs_than_the_mode()
+    {
+        let mut board = Board::new();
+        board.reset_with_seed(GridSize::SIX, 11);
+        let mut save = board.save_game().unwrap();
+        let extra = save.next[0].clone();
+        save.next.extend(vec![extra; 7]);
+        let mut resumed = Board::new();
+        resumed.resume(&save).unwrap();
+        let shown = GameRules::for_mode(GameMode::CLASSIC).next_pairs;
+        assert_eq!(resumed.get_rules().next_pairs, shown);
+        assert_eq!(resumed.get_elements().get_next_elements().len(), 2);
+        assert_eq!(resumed.get_elements().get_queue_elements().len(), 2 * (shown - 1));
+    }
+
+    #[test]
+    fn reagents_not_making_the_product_stay()
+    {
+        //Salt links to sulfur through the brimstone recipe, but three salts make sulfur on their own
+        let mut board = Board::new();
+        board.start_puzzle(&puzzle(4, "- - - sulfur / salt salt salt sulfur", "air water"));
+        board.drop_pair();
+        settle(&mut board);
+        assert_eq!(rows(&board), vec!["- - - -", "- - - -", "- - - sulfur", "sulfur air water sulfur"]);
+        //Only the salts score, sulfur's value for each
+        assert_eq!(board.get_score(), 3 * 9);
+    }
+
+    //Tiles the ghost of the pair in control shows, and the elements on them
+    fn ghost(board : &Board) -> Vec<(i32, i32, String)>
+    {
+        let data = ElementTypeList::new();
+        let elements = board.get_elements();
+        elements.get_ghost_elements().iter().map(|e|
+        {
+            let (x, y) = e.get_pos();
+            let (px, py) = elements.get_tile_at(x, y).unwrap();
+            (px, py, data.get_name(e.get_type()).to_owned())
+        }).collect()
+    }
+
+    #[test]
+    fn ghost_rests_on_top_of_a_tall_column()
+    {
+        let mut board = Board::new();
+        board.start_puzzle(&puzzle(4, "- water - - / - fire - - / - earth - -", "air salt"));
+        assert_eq!(ghost(&board), vec![(1, 3, "air".to_owned()), (2, 0, "salt".to_owned())]);
+        board.drop_pair();
+        settle(&mut board);
+        assert_eq!(rows(&board), vec!["- air - -", "- water - -", "- fire - -", "- earth salt -"]);
+    }
+
+    #[test]
+    fn ghost_stacks_a_standing_pair()
+    {
+        let mut board = Board::new();
+        board.start_puzzle(&puzzle(4, "- - earth -", "air salt"));
+        //Upright, the right element under the left one
+        board.rotate_pair();
+        board.move_pair(1);
+        assert_eq!(ghost(&board), vec![(2, 1, "salt".to_owned()), (2, 2, "air".to_owned())]);
+        board.drop_pair();
+        settle(&mut board);
+        assert_eq!(rows(&board), vec!["- - - -", "- - air -", "- - salt -", "- - earth -"]);
+    }
+
+    #[test]
+    fn huge_moves_stop_at_the_walls()
+    {
+        let mut board = Board::new();
+        board.start_puzzle(&puzzle(4, "- - - -", "air salt"));
+        board.move_pair(i32::MAX);
+        assert_eq!(ghost(&board), vec![(2, 0, "air".to_owned()), (3, 0, "salt".to_owned())]);
+        board.move_pair(i32::MIN);
+        assert_eq!(ghost(&board), vec![(0, 0, "air".to_owned()), (1, 0, "salt".to_owned())]);
+    }
+
+    //Whether the puzzle is solved once its only drop settles, with the pair dropped where it starts
+    fn solve(board_rows : &str, pair : &str, goal : &str) -> bool
+    {
+        let data = ElementTypeList::new();
+        let mut puzzle = puzzle(4, board_rows, pair);
+        puzzle.goal = match goal.split_whitespace().collect::<Vec<&str>>().as_slice()
+        {
+            ["make", name] => PuzzleGoal::MAKE(data.from_name(name).unwrap()),
+            ["clear", name] => PuzzleGoal::CLEAR(data.from_name(name).unwrap()),
+            _ => panic!("unknown goal {}", goal),
+        };
+        let mut board = Board::new();
+        board.start_puzzle(&puzzle);
+        board.drop_pair();
+        settle(&mut board);
+        assert!(board.is_game_over());
+        board.is_puzzle_solved()
+    }
+
+    #[test]
+    fn puzzle_goals_are_checked()
+    {
+        assert!(solve("air air - -", "air fire", "make salt"));
+        assert!(!solve("air air - -", "air fire", "make sulfur"));
+        //Ash goes with the earth reacting next to it
+        assert!(solve("ash earth earth -", "earth water", "clear ash"));
+        assert!(!solve("ash earth earth -", "water fire", "clear ash"));
+        assert!(!solve("ash earth earth -", "earth water", "clear water"));
+    }
+
+    //Bottom row once the puzzle's first pair is dropped, with products put where the rule says
+    fn place(placement : ProductPlacement, board_rows : &str, pair : &str) -> String
+    {
+        let mut board = Board::new();
+        board.start_puzzle(&puzzle(4, board_rows, pair));
+        let mut rules = GameRules::for_mode(GameMode::PUZZLE);
+        rules.product_placement = placement;
+        board.set_rules(rules);
+        board.drop_pair();
+        settle(&mut board);
+        rows(&board).pop().unwrap()
+    }
+
+    #[test]
+    fn lowest_placement_takes_the_bottom_left_cell()
+    {
+        assert_eq!(place(ProductPlacement::LOWEST, "air - - - / air - - water", "air fire"), "salt - fire water");
+        assert_eq!(place(ProductPlacement::LOWEST, "air - air -", "air air"), "salt - - -");
+    }
+
+    #[test]
+    fn last_dropped_placement_follows_the_pair()
+    {
+        assert_eq!(place(ProductPlacement::LAST_DROPPED, "air - - - / air - - water", "air fire"), "- salt fire water");
+    }
+
+    #[test]
+    fn centroid_placement_takes_the_middle_of_the_group()
+    {
+        assert_eq!(place(ProductPlacement::CENTROID, "air - air -", "air air"), "- salt - -");
+        //Two cells as close to the middle, the leftmost of the lowest wins
+        assert_eq!(place(ProductPlacement::CENTROID, "air - - air", "air air"), "- salt - -");
+    }
+
+    #[test]
+    fn time_attack_ends_when_the_clock_runs_out()
+    {
+        let mut board = Board::new();
+        board.set_rules(GameRules::for_mode(GameMode::TIME_ATTACK));
+        board.reset_with_seed(GridSize::SIX, 3);
+        for _ in 0..(181 * 20)
+        {
+            board.update(0.05);
+        }
+        assert!(board.is_game_over());
+        assert!(board.is_out_of_time());
+        assert_eq!(board.get_time_left(), Some(0.0));
+    }
+}
@@ -49,16 +49,52 @@ impl Camera
         self.adjust_aspect_ratio((width as f32)/(height as f32));
     }
 
+    //Same view, with what is drawn scaled then moved over to (x, y)
+    pub fn get_placed(&self, x: f32, y: f32, scale: f32) -> Camera
+    {
+        let placement = 
+        [
+            [scale, 0.0, 0.0, 0.0],
+            [0.0, scale, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [ x,   y,  0.0, 1.0]
+        ];
+        let mut view_matrix = [[0.0; 4]; 4];
+        for col in 0..4
+        {
+            for row in 0..4
+            {
+                view_matrix[col][row] = (0..4).map(|k| self.view_matrix[k][row] * placement[col][k]).sum();
+            }
+        }
+        Camera { view_matrix: view_matrix }
+    }
+
     pub fn get_pixel_coord(&self, x: f32, y:f32, w:f32, h:f32, dimx:u32, dimy:u32) -> Rect
     {
         let mx = self.view_matrix[0][0];
         let my = self.view_matrix[1][1];
+        let tx = self.view_matrix[3][0];
+        let ty = self.view_matrix[3][1];
 
-        let screenx = ((dimx as f32) * ((mx * x) + 1.0) / 2.0).floor() as u32;
-        let screeny = ((dimy as f32) * ((my * y) + 1.0) / 2.0).floor() as u32;
+        let screenx = ((dimx as f32) * ((mx * x) + tx + 1.0) / 2.0).floor() as u32;
+        let screeny = ((dimy as f32) * ((my * y) + ty + 1.0) / 2.0).floor() as u32;
         let screenw = ((dimx as f32) * ((mx * w)) / 2.0).floor() as u32;
         let screenh = ((dimy as f32) * ((my * h)) / 2.0).floor() as u32;
         
         Rect { left: screenx, bottom: screeny, width: screenw, height: screenh }
     }
+
+    //Back from window pixels, from the top left, to direct screen positions
+    pub fn get_world_coord(&self, px: f64, py: f64, dimx: u32, dimy: u32) -> (f32, f32)
+    {
+        let mx = self.view_matrix[0][0];
+        let my = self.view_matrix[1][1];
+        let tx = self.view_matrix[3][0];
+        let ty = self.view_matrix[3][1];
+
+        let x = (2.0 * (px as f32) / (dimx as f32) - 1.0 - tx) / mx;
+        let y = (1.0 - 2.0 * (py as f32) / (dimy as f32) - ty) / my;
+        (x, y)
+    }
 }
\ No newline at end of file
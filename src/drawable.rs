@@ -0,0 +1,8 @@
+extern crate glium;
+
+use camera;
+
+pub trait Drawable 
+{
+    fn draw(&self, &mut glium::Frame, &camera::Camera);
+}
@@ -0,0 +1,278 @@
+use board::GridSize;
+use element::{ElementType, ElementTypeList};
+use puzzle::{Puzzle, PuzzleGoal};
+
+//Settings of the puzzle being edited, each stepped forward or back through its values
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum EditorField
+{
+    SIZE,
+    GOAL, //Kind of goal
+    TARGET, //Element or points the goal asks for
+    DROPS,
+    PAIR, //Pair in view, among the sequence
+}
+
+//Puzzle being put together, one tile or element at a time
+//The board shows it from the pair in view: that one in control, the ones after it queued
+pub struct PuzzleEditor
+{
+    puzzle : Puzzle,
+    pair_index : usize,
+}
+
+impl PuzzleEditor
+{
+    pub fn new(puzzle : Puzzle) -> PuzzleEditor
+    {
+        PuzzleEditor
+        {
+            puzzle : puzzle,
+            pair_index : 0,
+        }
+    }
+    //Empty board, a single pair of the first starting element
+    pub fn blank(name : &str, data : &ElementTypeList) -> PuzzleEditor
+    {
+        let size = GridSize::SIX;
+        let width = size as i32;
+        let first = data.get_starting()[0];
+        PuzzleEditor::new(Puzzle
+        {
+            name : name.to_owned(),
+            grid_size : size,
+            tiles : vec![None; (width * (width + 2)) as usize],
+            pairs : vec![(first, first)],
+            goal : PuzzleGoal::SCORE(100),
+            drops : 1,
+        })
+    }
+
+    pub fn get_puzzle(&self) -> &Puzzle
+    {
+        &self.puzzle
+    }
+    pub fn get_pair_index(&self) -> usize
+    {
+        self.pair_index
+    }
+    //Puzzle as laid out on the board while editing, dealt from the pair in view
+    pub fn get_shown(&self) -> Puzzle
+    {
+        let mut shown = self.puzzle.clone();
+        shown.pairs = self.puzzle.pairs[self.pair_index..].to_vec();
+        shown
+    }
+
+    //Steps through the elements, empty included for tiles
+    fn cycle_element(t : Option<ElementType>, forward : bool, empty : bool, data : &ElementTypeList) -> Option<ElementType>
+    {
+        let mut choices : Vec<Option<ElementType>> = data.get_all().into_iter().map(Some).collect();
+        if empty
+        {
+            choices.insert(0, None);
+        }
+        let count = choices.len();
+        let index = choices.iter().position(|c| *c == t).unwrap_or(0);
+        if forward { choices[(index + 1) % count] } else { choices[(index + count - 1) % count] }
+    }
+    //Tile of the grid, or element of the pair in view where it waits above the grid
+    //Returns whether anything changed
+    pub fn cycle_tile(&mut self, px : i32, py : i32, forward : bool, data : &ElementTypeList) -> bool
+    {
+        let width = self.puzzle.grid_size as i32;
+        let height = width + 2;
+        if px < 0 || px >= width || py < 0
+        {
+            return false
+        }
+        if py < width
+        {
+            let index = (px * height + py) as usize;
+            self.puzzle.tiles[index] = PuzzleEditor::cycle_element(self.puzzle.tiles[index], forward, true, data);
+            return true
+        }
+        //Where a new pair spawns, on the top row
+        let pair = &mut self.puzzle.pairs[self.pair_index];
+        match (px - (width / 2 - 1), py == height - 1)
+        {
+            (0, true) => pair.0 = PuzzleEditor::cycle_element(Some(pair.0), forward, false, data).unwrap(),
+            (1, true) => pair.1 = PuzzleEditor::cycle_element(Some(pair.1), forward, false, data).unwrap(),
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn step(&mut self, field : EditorField, forward : bool, data : &ElementTypeList)
+    {
+        match field
+        {
+            EditorField::SIZE =>
+            {
+                let value = self.puzzle.grid_size as i32 + if forward { 1 } else { -1 };
+                if let Some(size) = GridSize::from_value(value)
+                {
+                    self.resize(size);
+                }
+            },
+            EditorField::GOAL =>
+            {
+                //Element goals keep their element from one to the other
+                let element = match self.puzzle.goal
+                {
+                    PuzzleGoal::MAKE(t) | PuzzleGoal::CLEAR(t) => t,
+                    PuzzleGoal::SCORE(_) => data.get_all()[0],
+                };
+                self.puzzle.goal = match (self.puzzle.goal, forward)
+                {
+                    (PuzzleGoal::MAKE(_), true) | (PuzzleGoal::SCORE(_), false) => PuzzleGoal::CLEAR(element),
+                    (PuzzleGoal::CLEAR(_), true) | (PuzzleGoal::MAKE(_), false) => PuzzleGoal::SCORE(100),
+                    (PuzzleGoal::SCORE(_), true) | (PuzzleGoal::CLEAR(_), false) => PuzzleGoal::MAKE(element),
+                };
+            },
+            EditorField::TARGET =>
+            {
+                self.puzzle.goal = match self.puzzle.goal
+                {
+                    PuzzleGoal::MAKE(t) => PuzzleGoal::MAKE(PuzzleEditor::cycle_element(Some(t), forward, false, data).unwrap()),
+                    PuzzleGoal::CLEAR(t) => PuzzleGoal::CLEAR(PuzzleEditor::cycle_element(Some(t), forward, false, data).unwrap()),
+                    PuzzleGoal::SCORE(points) if forward => PuzzleGoal::SCORE(points + 50),
+                    PuzzleGoal::SCORE(points) => PuzzleGoal::SCORE(points.saturating_sub(50).max(50)),
+                };
+            },
+            EditorField::DROPS =>
+            {
+                let count = self.puzzle.pairs.len() as u32;
+                self.puzzle.drops = if forward { self.puzzle.drops % count + 1 } else { (self.puzzle.drops + count - 2) % count + 1 };
+            },
+            EditorField::PAIR =>
+            {
+                let count = self.puzzle.pairs.len();
+                self.pair_index = if forward { (self.pair_index + 1) % count } else { (self.pair_index + count - 1) % count };
+            },
+        }
+    }
+    //Copy of the pair in view right after it, now in view
+    pub fn add_pair(&mut self)
+    {
+        //A puzzle using all its pairs keeps doing so
+        if self.puzzle.drops as usize == self.puzzle.pairs.len()
+        {
+            self.puzzle.drops += 1;
+        }
+        let pair = self.puzzle.pairs[self.pair_index];
+        self.pair_index += 1;
+        self.puzzle.pairs.insert(self.pair_index, pair);
+    }
+    //There is always one left
+    pub fn remove_pair(&mut self)
+    {
+        if self.puzzle.pairs.len() > 1
+        {
+            self.puzzle.pairs.remove(self.pair_index);
+            self.pair_index = self.pair_index.min(self.puzzle.pairs.len() - 1);
+            self.puzzle.drops = self.puzzle.drops.min(self.puzzle.pairs.len() as u32);
+        }
+    }
+    //Tiles stay where they are from the bottom left, those out of the grid are lost
+    fn resize(&mut self, size : GridSize)
+    {
+        let (old_width, width) = (self.puzzle.grid_size as i32, size as i32);
+        let mut tiles = vec![None; (width * (width + 2)) as usize];
+        for px in 0..old_width.min(width)
+        {
+            for py in 0..old_width.min(width)
+            {
+                tiles[(px * (width + 2) + py) as usize] = self.puzzle.tiles[(px * (old_width + 2) + py) as usize];
+            }
+        }
+        self.puzzle.grid_size = size;
+        self.puzzle.tiles = tiles;
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use element_array::ElementArray;
+
+    #[test]
+    fn tiles_cycle_through_the_elements_and_empty()
+    {
+        let data = ElementTypeList::new();
+        let all = data.get_all();
+        let mut editor = PuzzleEditor::blank("test", &data);
+        assert!(editor.cycle_tile(2, 1, true, &data));
+        assert_eq!(editor.get_puzzle().tiles[2 * 8 + 1], Some(all[0]));
+        assert!(editor.cycle_tile(2, 1, false, &data));
+        assert_eq!(editor.get_puzzle().tiles[2 * 8 + 1], None);
+        //Back from empty wraps around to the last element
+        assert!(editor.cycle_tile(2, 1, false, &data));
+        assert_eq!(editor.get_puzzle().tiles[2 * 8 + 1], all.last().cloned());
+    }
+
+    #[test]
+    fn pair_cycles_on_the_top_row_only()
+    {
+        let data = ElementTypeList::new();
+        let all = data.get_all();
+        let mut editor = PuzzleEditor::blank("test", &data);
+        let first = editor.get_puzzle().pairs[0];
+        //Six wide: the pair spawns over columns 2 and 3 of the top row
+        assert!(editor.cycle_tile(3, 7, true, &data));
+        let index = all.iter().position(|t| *t == first.1).unwrap();
+        assert_eq!(editor.get_puzzle().pairs[0], (first.0, all[(index + 1) % all.len()]));
+        //Never empty: back from the first element to the last
+        assert!(editor.cycle_tile(2, 7, false, &data));
+        let index = all.iter().position(|t| *t == first.0).unwrap();
+        assert_eq!(editor.get_puzzle().pairs[0].0, all[(index + all.len() - 1) % all.len()]);
+        //Elsewhere above the grid, or outside of it
+        assert!(!editor.cycle_tile(0, 7, true, &data));
+        assert!(!editor.cycle_tile(2, 6, true, &data));
+        assert!(!editor.cycle_tile(-1, 0, true, &data));
+        assert!(!editor.cycle_tile(6, 0, true, &data));
+    }
+
+    #[test]
+    fn resizing_keeps_tiles_from_the_bottom_left()
+    {
+        let data = ElementTypeList::new();
+        let salt = data.from_name("salt");
+        let mut editor = PuzzleEditor::blank("test", &data);
+        //Six wide and eight high: a corner tile and the far ones
+        editor.puzzle.tiles[0] = salt;
+        editor.puzzle.tiles[5 * 8] = salt;
+        editor.puzzle.tiles[5] = salt;
+        editor.puzzle.tiles[3 * 8 + 3] = salt;
+        editor.step(EditorField::SIZE, false, &data);
+        assert_eq!(editor.get_puzzle().grid_size, GridSize::FIVE);
+        //Five wide and seven high: the last column and row are gone
+        let tiles = &editor.get_puzzle().tiles;
+        assert_eq!(tiles.len(), 5 * 7);
+        assert_eq!(tiles[0], salt);
+        assert_eq!(tiles[3 * 7 + 3], salt);
+        assert_eq!(tiles.iter().filter(|t| t.is_some()).count(), 2);
+        //Growing back brings nothing back
+        editor.step(EditorField::SIZE, true, &data);
+        assert_eq!(editor.get_puzzle().tiles.iter().filter(|t| t.is_some()).count(), 2);
+    }
+
+    #[test]
+    fn clicks_map_to_tiles()
+    {
+        let array = ElementArray::new(&GridSize::SIX);
+        let (ox, oy) = ElementArray::ORIGIN;
+        //Tiles are two wide, the grid centered on the origin
+        let point = |px : i32, py : i32| (ox - 6.0 + 2.0 * px as f32 + 1.0, oy - 6.0 + 2.0 * py as f32 + 1.0);
+        for &(px, py) in &[(0, 0), (5, 0), (2, 5), (3, 7)]
+        {
+            let (x, y) = point(px, py);
+            assert_eq!(array.get_tile_at(x, y), Some((px, py)));
+        }
+        assert_eq!(array.get_tile_at(ox - 6.5, oy), None);
+        assert_eq!(array.get_tile_at(ox + 6.5, oy), None);
+        assert_eq!(array.get_tile_at(ox, oy - 6.5), None);
+        assert_eq!(array.get_tile_at(ox, oy + 10.5), None);
+    }
+}
@@ -1,285 +1,381 @@
-use vertex::{TextureVertex, Square};
+use sections;
+use sections::{Entry, Section};
+use std::fs;
+use std::path::{Path, PathBuf};
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
-pub enum ElementType
-{
-    AIR,
-    WATER,
-    EARTH,
-    FIRE,
-    SALT,
-    SULFUR,
-    MERCURY,
-    LEAD,
-    TIN,
-    IRON,
-    COPPER,
-    SILVER,
-    GOLD,
-    
-    ASH,
-    ANTIMONY,
-    AETHER,
-}
+//Id of an element in its ElementTypeList, in the order of its definition file
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ElementType(pub usize);
 
+//Registry of the elements a game is played with
+#[derive(Clone)]
 pub struct ElementTypeList
 {
-    pub AIR : ElementTypeData,
-    pub WATER : ElementTypeData,
-    pub EARTH : ElementTypeData,
-    pub FIRE : ElementTypeData,
-    pub SALT : ElementTypeData,
-    pub SULFUR : ElementTypeData,
-    pub MERCURY : ElementTypeData,
-    pub LEAD : ElementTypeData,
-    pub TIN : ElementTypeData,
-    pub IRON : ElementTypeData,
-    pub COPPER : ElementTypeData,
-    pub SILVER : ElementTypeData,
-    pub GOLD : ElementTypeData,
-
-    pub ASH : ElementTypeData,
-    pub ANTIMONY : ElementTypeData,
-    pub AETHER : ElementTypeData,
+    types : Vec<ElementTypeData>,
+    starting : Vec<ElementType>, //Unlocked when a game starts
+    recipes : Vec<Recipe>, //Checked in order, before the elements' own products
+    garbage : Option<ElementType>, //Dropped in as a penalty
 }
 impl ElementTypeList
 {
+    //Definitions shipped with the game, copied from content/elements.txt at build time
     pub fn new() -> ElementTypeList
     {
-        ElementTypeList
+        ElementTypeList::parse(include_str!("../content/elements.txt"), "built-in elements").unwrap()
+    }
+
+    pub fn get_default_path() -> PathBuf
+    {
+        PathBuf::from("./content/elements.txt")
+    }
+    pub fn load(path : &Path) -> Result<ElementTypeList, String>
+    {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        ElementTypeList::parse(&text, &path.display().to_string())
+    }
+
+    //One section per element or recipe, see content/elements.txt for the format
+    pub fn parse(text : &str, source : &str) -> Result<ElementTypeList, String>
+    {
+        let all_sections = sections::parse(text, source)?;
+        let (recipe_sections, sections) : (Vec<&Section>, Vec<&Section>) = all_sections.iter()
+            .partition(|s| s.name == "recipe" || s.name.starts_with("recipe "));
+        //Names first, so elements can refer to ones defined further down
+        let mut names : Vec<&str> = vec![];
+        for section in &sections
         {
-            AIR : ElementTypeData
+            if section.name.contains(char::is_whitespace)
             {
-                x_offset: 0.00,
-                y_offset: 0.75,
-                value: 1,
-                weight: 20,
-                interacts: vec![ElementType::AIR],
-                produces: Option::Some(ElementType::SALT),
-            },
-            FIRE : ElementTypeData
+                return Err(format!("{}:{}: [{}] cannot have spaces in its name", source, section.line, section.name))
+            }
+            if names.contains(&section.name.as_str())
             {
-                x_offset: 0.25,
-                y_offset: 0.75,
-                value: 1,
-                weight: 20,
-                interacts: vec![ElementType::FIRE],
-                produces: Option::Some(ElementType::SALT),
-            },
-            WATER : ElementTypeData
+                return Err(format!("{}:{}: [{}] is defined twice", source, section.line, section.name))
+            }
+            names.push(&section.name);
+        }
+        let find = |name : &str, entry : &Entry| match names.iter().position(|n| n.eq_ignore_ascii_case(name))
+        {
+            Some(i) => Ok(ElementType(i)),
+            None => Err(entry.error(source, &format!("unknown element \"{}\"", name))),
+        };
+
+        let mut list = ElementTypeList { types : vec![], starting : vec![], recipes : vec![], garbage : None };
+        for section in &sections
+        {
+            section.check_keys(&["atlas", "value", "weight", "start", "unlocked_by", "min_group", "wildcard", "catalyst", "garbage", "interacts", "produces"], source)?;
+
+            let atlas = section.require("atlas", source)?;
+            let offsets = atlas.words().iter().map(|w| w.parse::<f32>()).collect::<Result<Vec<f32>, _>>()
+                .map_err(|_| atlas.error(source, "expected two numbers"))?;
+            if offsets.len() != 2 || offsets.iter().any(|o| *o < 0.0 || *o > 0.75)
             {
-                x_offset: 0.50,
-                y_offset: 0.75,
-                value: 1,
-                weight: 20,
-                interacts: vec![ElementType::WATER],
-                produces: Option::Some(ElementType::SALT),
-            },
-            EARTH : ElementTypeData
+                return Err(atlas.error(source, "expected two offsets between 0.00 and 0.75"))
+            }
+            let interacts = match section.get("interacts")
             {
-                x_offset: 0.75,
-                y_offset: 0.75,
-                value: 1,
-                weight: 20,
-                interacts: vec![ElementType::EARTH],
-                produces: Option::Some(ElementType::SALT),
-            },
-            SALT : ElementTypeData
+                Some(entry) => entry.words().iter().map(|w| find(w, entry)).collect::<Result<Vec<ElementType>, String>>()?,
+                None => vec![],
+            };
+            let produces = match section.get("produces")
             {
-                x_offset: 0.00,
-                y_offset: 0.50,
-                value: 3,
-                weight: 40,
-                interacts: vec![ElementType::SALT],
-                produces: Option::Some(ElementType::SULFUR),
-            },
-            SULFUR : ElementTypeData
+                Some(entry) => Some(find(&entry.value, entry)?),
+                None => None,
+            };
+            let start = match section.get("start")
             {
-                x_offset: 0.25,
-                y_offset: 0.50,
-                value: 9,
-                weight: 24,
-                interacts: vec![ElementType::SULFUR],
-                produces: Option::Some(ElementType::MERCURY),
-            },
-            MERCURY : ElementTypeData
+                Some(entry) => entry.parse::<bool>(source)?,
+                None => false,
+            };
+            let unlocked_by = match section.get("unlocked_by")
             {
-                x_offset: 0.50,
-                y_offset: 0.50,
-                value: 27,
-                weight: 16,
-                interacts: vec![ElementType::MERCURY],
-                produces: Option::Some(ElementType::LEAD),
-            },
-            LEAD : ElementTypeData
+                Some(entry) => entry.words().iter().map(|w| find(w, entry)).collect::<Result<Vec<ElementType>, String>>()?,
+                None => vec![],
+            };
+            let wildcard = match section.get("wildcard")
             {
-                x_offset: 0.00,
-                y_offset: 0.25,
-                value: 81,
-                weight: 12,
-                interacts: vec![ElementType::LEAD],
-                produces: Option::Some(ElementType::TIN),
-            },
-            TIN : ElementTypeData
+                Some(entry) => entry.parse::<bool>(source)?,
+                None => false,
+            };
+            let catalyst = match section.get("catalyst")
             {
-                x_offset: 0.25,
-                y_offset: 0.25,
-                value: 243,
-                weight: 9,
-                interacts: vec![ElementType::TIN],
-                produces: Option::Some(ElementType::IRON),
-            },
-            IRON : ElementTypeData
+                Some(entry) => Some(entry.parse::<usize>(source)?),
+                None => None,
+            };
+            if catalyst == Some(0)
             {
-                x_offset: 0.50,
-                y_offset: 0.25,
-                value: 729,
-                weight: 6,
-                interacts: vec![ElementType::IRON],
-                produces: Option::Some(ElementType::COPPER),
-            },
-            COPPER : ElementTypeData
+                return Err(section.require("catalyst", source)?.error(source, "a catalyst needs at least 1 element to act on"))
+            }
+            if wildcard && catalyst.is_some()
             {
-                x_offset: 0.00,
-                y_offset: 0.00,
-                value: 2187,
-                weight: 3,
-                interacts: vec![ElementType::COPPER],
-                produces: Option::Some(ElementType::SILVER),
-            },
-            SILVER : ElementTypeData
+                return Err(format!("{}:{}: [{}] cannot be both a wildcard and a catalyst", source, section.line, section.name))
+            }
+            let min_group = match section.get("min_group")
             {
-                x_offset: 0.25,
-                y_offset: 0.00,
-                value: 6561,
-                weight: 1,
-                interacts: vec![ElementType::SILVER],
-                produces: Option::Some(ElementType::GOLD),
-            },
-            GOLD : ElementTypeData
+                Some(entry) => entry.parse::<usize>(source)?,
+                None => 3,
+            };
+            if min_group < 2
             {
-                x_offset: 0.50,
-                y_offset: 0.00,
-                value: 19683,
-                weight: 0,
-                interacts: vec![],
-                produces: Option::None,
-            },
-            ASH : ElementTypeData
+                return Err(section.require("min_group", source)?.error(source, "a group needs at least 2 elements"))
+            }
+
+            let garbage = match section.get("garbage")
             {
-                x_offset: 0.75,
-                y_offset: 0.50,
-                value: 0,
-                weight: 40,
-                interacts: vec![ElementType::ASH],
-                produces: Option::Some(ElementType::ASH),
-            },
-            ANTIMONY : ElementTypeData
+                Some(entry) => entry.parse::<bool>(source)?,
+                None => false,
+            };
+            if garbage && list.garbage.is_some()
             {
-                x_offset: 0.75,
-                y_offset: 0.25,
-                value: 0,
-                weight: 10,
-                //All metals except Gold
-                interacts: vec![ElementType::MERCURY, ElementType::LEAD, ElementType::TIN, ElementType::IRON, ElementType::COPPER, ElementType::SILVER],
-                produces: Option::None,
-            },
-            AETHER : ElementTypeData
+                return Err(format!("{}:{}: [{}] cannot be garbage, another element already is", source, section.line, section.name))
+            }
+
+            if start
             {
-                x_offset: 0.75,
-                y_offset: 0.00,
-                value: 0,
-                weight: 20,
-                interacts: vec![ElementType::AIR, ElementType::EARTH, ElementType::FIRE, ElementType::WATER],
-                produces: Option::None,
-            },
+                list.starting.push(ElementType(list.types.len()));
+            }
+            if garbage
+            {
+                list.garbage = Some(ElementType(list.types.len()));
+            }
+            list.types.push(ElementTypeData
+            {
+                name : section.name.clone(),
+                x_offset : offsets[0],
+                y_offset : offsets[1],
+                value : section.require("value", source)?.parse::<u32>(source)?,
+                weight : section.require("weight", source)?.parse::<u32>(source)?,
+                unlocked_by : unlocked_by,
+                min_group : min_group,
+                wildcard : wildcard,
+                catalyst : catalyst,
+                interacts : interacts,
+                produces : produces,
+            });
         }
+
+        for section in recipe_sections
+        {
+            section.check_keys(&["ingredients", "produces"], source)?;
+            let entry = section.require("ingredients", source)?;
+            //Repeated names count as more of the same ingredient
+            let mut ingredients : Vec<(ElementType, usize)> = vec![];
+            for word in entry.words()
+            {
+                let t = find(word, entry)?;
+                match ingredients.iter_mut().find(|&&mut (i, _)| i == t)
+                {
+                    Some(&mut (_, ref mut count)) => *count += 1,
+                    None => ingredients.push((t, 1)),
+                }
+            }
+            if entry.words().len() < 2
+            {
+                return Err(entry.error(source, "a recipe needs at least 2 ingredients"))
+            }
+            let produces = section.require("produces", source)?;
+            list.recipes.push(Recipe
+            {
+                ingredients : ingredients,
+                produces : find(&produces.value, produces)?,
+            });
+        }
+
+        //Something has to spawn when a game starts
+        if list.starting.iter().all(|t| list.get_data(t).weight == 0)
+        {
+            return Err(format!("{}: at least one element needs \"start = true\" and a weight above 0", source))
+        }
+        Ok(list)
     }
 
     pub fn get_data(&self, t : &ElementType) -> &ElementTypeData
     {
-        match *t
-        {
-            ElementType::AIR => &self.AIR,
-            ElementType::FIRE => &self.FIRE,
-            ElementType::WATER => &self.WATER,
-            ElementType::EARTH => &self.EARTH,
-            ElementType::SALT => &self.SALT,
-            ElementType::SULFUR => &self.SULFUR,
-            ElementType::MERCURY => &self.MERCURY,
-            ElementType::LEAD => &self.LEAD,
-            ElementType::TIN => &self.TIN,
-            ElementType::IRON => &self.IRON,
-            ElementType::COPPER => &self.COPPER,
-            ElementType::SILVER => &self.SILVER,
-            ElementType::GOLD => &self.GOLD,
-            ElementType::ASH => &self.ASH,
-            ElementType::AETHER => &self.AETHER,
-            ElementType::ANTIMONY => &self.ANTIMONY,
-        }
+        &self.types[t.0]
+    }
+    pub fn get_all(&self) -> Vec<ElementType>
+    {
+        (0..self.types.len()).map(ElementType).collect()
+    }
+    pub fn get_starting(&self) -> &Vec<ElementType>
+    {
+        &self.starting
+    }
+    pub fn get_garbage(&self) -> Option<ElementType>
+    {
+        self.garbage
+    }
+    //Name used in files and on screen
+    pub fn get_name(&self, t : &ElementType) -> &str
+    {
+        &self.get_data(t).name
+    }
+    pub fn from_name(&self, name : &str) -> Option<ElementType>
+    {
+        self.types.iter().position(|d| d.name.eq_ignore_ascii_case(name)).map(ElementType)
     }
 
-    pub fn get_element(&self, unlocks : &Vec<ElementType>) -> ElementType
+    //Fingerprint of everything about the elements that plays a part in a game, sprites aside (FNV-1a)
+    //Replays are only played back with the elements they were recorded with
+    pub fn get_hash(&self) -> u64
     {
-        let mut weights : Vec<u32> = Vec::with_capacity(unlocks.len());
-        let mut total = 0.0;
-        for i in 0..unlocks.len()
+        let ids = |types : &Vec<ElementType>| types.iter().map(|t| t.0.to_string()).collect::<Vec<String>>().join(",");
+        let mut text = String::new();
+        for t in self.get_all()
         {
-            total += self.get_data(&unlocks[i]).weight as f32;
-            weights.push(self.get_data(&unlocks[i]).weight);
+            let d = self.get_data(&t);
+            text.push_str(&format!("{} {} {} {} {} {} {} {} [{}] [{}] {}\n", d.name, d.value, d.weight, d.min_group, d.wildcard,
+                d.catalyst.map_or(-1, |c| c as i64), self.starting.contains(&t), self.garbage == Some(t),
+                ids(&d.unlocked_by), ids(&d.interacts), d.produces.map_or(-1, |p| p.0 as i64)));
         }
-        use rand::Rng;
-        let random = rand::thread_rng().gen_range(0.0, total.into());
-
-        let mut temp = 0;
-        for i in 0..weights.len()
+        for recipe in &self.recipes
         {
-            temp += weights[i];
-            if temp as f32 > random
-            {
-                return unlocks[i as usize]
-            }
+            let ingredients : Vec<String> = recipe.ingredients.iter().map(|&(t, count)| format!("{}x{}", t.0, count)).collect();
+            text.push_str(&format!("recipe {} {}\n", ingredients.join(","), recipe.produces.0));
         }
-
-        println!("HELP! GET ELEMENT FAILED!");
-        ElementType::ASH
+        let mut hash : u64 = 0xCBF2_9CE4_8422_2325;
+        for byte in text.bytes()
+        {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01B3);
+        }
+        hash
     }
 
+    //Ingredients of a same recipe group together as well
     pub fn can_react(&self, e1:&ElementType, e2:&ElementType) -> bool
     {
         self.get_data(e1).interacts.contains(e2) || self.get_data(e2).interacts.contains(e1)
+        || self.recipes.iter().any(|r| r.uses(e1) && r.uses(e2))
+    }
+    //Whether an element reacting with `from` may join the group of reagents found so far
+    //Wildcards and catalysts complete one group, they do not bridge two different ones through themselves
+    pub fn can_join(&self, from:&ElementType, t:&ElementType, reagents : &Vec<ElementType>) -> bool
+    {
+        if !self.can_react(from, t)
+        {
+            return false
+        }
+        !self.is_joker(from)
+        || reagents.iter().filter(|r| !self.is_joker(r)).all(|r| self.can_react(r, t))
+    }
+    fn is_joker(&self, t:&ElementType) -> bool
+    {
+        let data = self.get_data(t);
+        data.wildcard || data.catalyst.is_some()
+    }
+    //Whether a catalyst took part in a group
+    pub fn is_catalyzed(&self, reagents : &Vec<ElementType>) -> bool
+    {
+        reagents.iter().any(|r| self.get_data(r).catalyst.is_some())
+    }
+    //Elements that start spawning once `t` has been made
+    pub fn get_unlocked_by(&self, t:&ElementType) -> Vec<ElementType>
+    {
+        self.get_all().into_iter().filter(|u| self.get_data(u).unlocked_by.contains(t)).collect()
     }
+    //What a group turns into, if it reacts at all
     pub fn get_product(&self, reagents : &Vec<ElementType>) -> Option<ElementType>
     {
-        let mut product = None;
-        for i in 0..reagents.len()
+        if let Some(recipe) = self.recipes.iter().find(|r| r.matches(reagents))
         {
-            let p = self.get_data(&reagents[i]).produces;
-            if p.is_some() && (product.is_none() || self.get_data(product.as_ref().unwrap()).value < self.get_data(p.as_ref().unwrap()).value)
+            return Some(recipe.produces)
+        }
+
+        //Otherwise the most valuable product the group is large enough for, counting the reagents making it and the wildcards
+        //With catalysts in the group, they decide how many are needed instead
+        let catalyst = reagents.iter().filter_map(|r| self.get_data(r).catalyst).min();
+        let wildcards = reagents.iter().filter(|r| self.get_data(r).wildcard).count();
+        let mut products : Vec<ElementType> = vec![];
+        for r in reagents
+        {
+            if let Some(p) = self.get_data(r).produces
             {
-                product = p;
+                if !products.contains(&p) { products.push(p); }
             }
         }
-        product
+        products.sort_by(|a, b| self.get_data(b).value.cmp(&self.get_data(a).value));
+        for p in products
+        {
+            let makers : Vec<&ElementType> = reagents.iter().filter(|r| self.get_data(r).produces == Some(p)).collect();
+            let needed = catalyst.unwrap_or_else(|| makers.iter().map(|r| self.get_data(r).min_group).max().unwrap());
+            if makers.len() + wildcards >= needed
+            {
+                return Some(p)
+            }
+        }
+        None
+    }
+    //Whether a reagent of a group turning into `product` is used up by it
+    //A recipe takes its whole group, otherwise only the reagents making the product and the jokers do
+    pub fn is_consumed(&self, reagent : &ElementType, product : &ElementType, reagents : &Vec<ElementType>) -> bool
+    {
+        self.is_joker(reagent) || self.get_data(reagent).produces == Some(*product)
+        || self.recipes.iter().any(|r| r.matches(reagents) && r.produces == *product)
     }
 }
 
+#[derive(Clone)]
 pub struct ElementTypeData
 {
+    name : String,
     x_offset : f32,
     y_offset : f32,
     value : u32,
     weight : u32,
+    unlocked_by : Vec<ElementType>, //Making any of these adds it to the spawns
+    min_group : usize, //Elements needed for its product to form
+    wildcard : bool, //Takes the part of whatever it joins, has no product of its own
+    catalyst : Option<usize>, //Elements needed for the groups it joins to react, not counting itself
     interacts : Vec<ElementType>,
     produces : Option<ElementType>,
 }
+impl ElementTypeData
+{
+    //Position of the sprite in the element atlas
+    pub fn get_atlas_offset(&self) -> (f32, f32)
+    {
+        (self.x_offset, self.y_offset)
+    }
+    pub fn get_value(&self) -> u32
+    {
+        self.value
+    }
+    pub fn get_weight(&self) -> u32
+    {
+        self.weight
+    }
+}
 
+//A group of exactly these ingredients, as many of each and nothing else, makes the product
+#[derive(Clone)]
+pub struct Recipe
+{
+    ingredients : Vec<(ElementType, usize)>,
+    produces : ElementType,
+}
+impl Recipe
+{
+    fn uses(&self, t : &ElementType) -> bool
+    {
+        self.ingredients.iter().any(|&(i, _)| i == *t)
+    }
+    fn matches(&self, reagents : &Vec<ElementType>) -> bool
+    {
+        reagents.iter().all(|t| self.uses(t))
+        && self.ingredients.iter().all(|&(i, count)| reagents.iter().filter(|t| **t == i).count() == count)
+    }
+}
+
+#[derive(Clone)]
 pub struct Element
 {
     x : f32,
     y : f32,
     t : ElementType,
     fallspeed : f32,
+    glow : f32, //Fades from 1 to 0 after a catalyzed reaction
+    recent : bool, //Part of the last drop, or made by its reactions
 }
 impl Element
 {
@@ -291,33 +387,18 @@ impl Element
             y : y,
             t : t,
             fallspeed : 0.0,
+            glow : 0.0,
+            recent : false,
         }
     }
 
-    pub fn get_vertices(&self, dataref : &ElementTypeList) -> Vec<TextureVertex>
-    {
-        let s = 2.0;
-        let depth = 1.0;
-        let dt = 0.25;
-
-        let data = dataref.get_data(&self.t);
-        let tx = data.x_offset;
-        let ty = data.y_offset;
-
-        let sq = Square {
-            top_left:     TextureVertex { position: [ self.x,  self.y+s, depth], tex_coords: [ tx, ty+dt ] },
-            top_right:    TextureVertex { position: [self.x+s, self.y+s, depth], tex_coords: [ tx+dt, ty+dt ] },
-            bottom_left:  TextureVertex { position: [ self.x,   self.y,  depth], tex_coords: [ tx, ty ] },
-            bottom_right: TextureVertex { position: [self.x+s,  self.y,  depth], tex_coords: [ tx+dt, ty ] },
-        };
-        sq.get_vec()
-    }
-
     pub fn move_to(&mut self, target_x:f32, target_y:f32, dt:f32, top:bool) -> bool
     {
         let mindistance = 0.01;
         let gravity = 50.0; //Adjustable
         let init_speed = 100.0;
+        let glow_fade = 1.5; //Per second
+        self.glow = (self.glow - dt * glow_fade).max(0.0);
         //Euclidian distance
         let dist = ((self.x - target_x).powi(2) + (self.y - target_y).powi(2)).sqrt();
         if dist < mindistance
@@ -352,8 +433,118 @@ impl Element
         self.x = new_x;
         self.y = new_y;
     }
+    pub fn get_pos(&self) -> (f32, f32)
+    {
+        (self.x, self.y)
+    }
     pub fn get_type(&self) -> &ElementType
     {
         &self.t
     }
-}
\ No newline at end of file
+    pub fn set_glow(&mut self, glow:f32)
+    {
+        self.glow = glow;
+    }
+    pub fn get_glow(&self) -> f32
+    {
+        self.glow
+    }
+    pub fn set_recent(&mut self, recent:bool)
+    {
+        self.recent = recent;
+    }
+    pub fn is_recent(&self) -> bool
+    {
+        self.recent
+    }
+}
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn named(data : &ElementTypeList, names : &[&str]) -> Vec<ElementType>
+    {
+        names.iter().map(|n| data.from_name(n).unwrap()).collect()
+    }
+
+    #[test]
+    fn recipe_needs_its_exact_ingredients()
+    {
+        let data = ElementTypeList::new();
+        let mercury = data.from_name("mercury");
+        assert_eq!(data.get_product(&named(&data, &["salt", "sulfur", "salt"])), mercury);
+        assert!(data.can_react(&named(&data, &["salt"])[0], &named(&data, &["sulfur"])[0]));
+        //One too few, or one too many
+        assert_eq!(data.get_product(&named(&data, &["salt", "sulfur"])), None);
+        assert_eq!(data.get_product(&named(&data, &["sulfur", "sulfur", "salt"])), None);
+        assert_eq!(data.get_product(&named(&data, &["salt", "salt", "sulfur", "sulfur"])), None);
+        //Enough salt on its own still makes what salt makes, and the sulfur is left out of it
+        let group = named(&data, &["salt", "salt", "salt", "sulfur"]);
+        let sulfur = data.from_name("sulfur").unwrap();
+        assert_eq!(data.get_product(&group), Some(sulfur));
+        assert!(data.is_consumed(&group[0], &sulfur, &group));
+        assert!(!data.is_consumed(&group[3], &sulfur, &group));
+        //A recipe takes all of its ingredients
+        let group = named(&data, &["salt", "sulfur", "salt"]);
+        assert!(group.iter().all(|r| data.is_consumed(r, &mercury.unwrap(), &group)));
+    }
+
+    #[test]
+    fn only_the_reagents_making_a_product_count_for_it()
+    {
+        let data = ElementTypeList::new();
+        let salt = data.from_name("salt");
+        assert_eq!(data.get_product(&named(&data, &["air", "air", "air"])), salt);
+        assert_eq!(data.get_product(&named(&data, &["air", "air"])), None);
+        //Wildcards count for any product
+        assert_eq!(data.get_product(&named(&data, &["air", "air", "aether"])), salt);
+        //Catalysts decide how many are needed
+        assert_eq!(data.get_product(&named(&data, &["antimony", "lead"])), data.from_name("tin"));
+        assert_eq!(data.get_product(&named(&data, &["antimony"])), None);
+    }
+
+    //Parsing fails with a message holding `expected`
+    fn fails(text : &str, expected : &str)
+    {
+        match ElementTypeList::parse(text, "test")
+        {
+            Ok(_) => panic!("parsed without error:\n{}", text),
+            Err(e) => assert!(e.contains(expected), "\"{}\" does not mention \"{}\"", e, expected),
+        }
+    }
+    const SALT : &str = "[salt]\natlas = 0.00 0.50\nvalue = 3\nweight = 1\nstart = true\n";
+
+    #[test]
+    fn smallest_definition_parses()
+    {
+        let data = ElementTypeList::parse(SALT, "test").unwrap();
+        assert_eq!(data.get_all().len(), 1);
+        assert_eq!(data.get_name(&data.get_starting()[0]), "salt");
+    }
+
+    #[test]
+    fn broken_definitions_are_refused()
+    {
+        fails(&format!("{}\n{}", SALT, SALT), "test:7: [salt] is defined twice");
+        fails("", "at least one element");
+        fails("[salt]\natlas = 0.00 0.50\nvalue = 3\nweight = 1\n", "at least one element");
+        fails("[salt]\natlas = 0.00 0.50\nvalue = 3\nstart = true\n", "[salt] is missing \"weight\"");
+        fails(&SALT.replace("weight = 1", "weight = -1"), "test:4: weight: invalid value \"-1\"");
+        fails(&SALT.replace("weight = 1", "weight = lots"), "invalid value \"lots\"");
+        fails(&SALT.replace("weight = 1", "weight = 0"), "at least one element");
+        fails(&format!("{}colour = white\n", SALT), "test:6: unknown key \"colour\" in [salt]");
+        fails(&format!("{}value = 4\n", SALT), "\"value\" is set twice");
+        fails(&format!("{}produces = gold\n", SALT), "unknown element \"gold\"");
+        fails(&format!("value = 3\n{}", SALT), "test:1: \"value\" is outside of any [section]");
+        fails(&format!("{}[salt\n", SALT), "expected a section name");
+        fails(&format!("{}min_group = 1\n", SALT), "at least 2 elements");
+    }
+
+    #[test]
+    fn recipes_must_name_known_elements()
+    {
+        let text = "[salt]\nstart = true\nweight = 1\n\n[recipe]\ningredients = salt lead\nproduces = salt\n";
+        assert!(ElementTypeList::parse(text, "test").is_err());
+    }
+}
@@ -1,13 +1,14 @@
-extern crate glium;
-
-use grid::GridSize;
+use board::GridSize;
 use element::{Element, ElementType, ElementTypeList};
-use glium::VertexBuffer;
-use glium::texture::Texture2d;
-use vertex::TextureVertex;
-use loader;
+use rng::SeededRng;
+use rules::{GameRules, ProductPlacement, MAX_NEXT_PAIRS};
+use spawn::{FixedSpawn, SpawnPolicy};
+use puzzle::Puzzle;
+use save::SaveGame;
+use std::collections::VecDeque;
 use std::mem;
 
+#[derive(Clone)]
 pub struct Tile
 {
     x : f32,
@@ -27,6 +28,7 @@ impl PartialEq for Coord {
     }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum GuideRotation
 {
     // Position of pair_1, relative to pair_2
@@ -35,7 +37,41 @@ pub enum GuideRotation
     RIGHT,
     DOWN,
 }
+impl GuideRotation
+{
+    pub fn get_name(&self) -> &'static str
+    {
+        match *self
+        {
+            GuideRotation::LEFT => "left",
+            GuideRotation::UP => "up",
+            GuideRotation::RIGHT => "right",
+            GuideRotation::DOWN => "down",
+        }
+    }
+    pub fn from_name(name : &str) -> Option<GuideRotation>
+    {
+        match name
+        {
+            "left" => Some(GuideRotation::LEFT),
+            "up" => Some(GuideRotation::UP),
+            "right" => Some(GuideRotation::RIGHT),
+            "down" => Some(GuideRotation::DOWN),
+            _ => None,
+        }
+    }
+}
 
+//What dropping the pair in control would do, worked out on a copy of the board
+pub struct DropPreview
+{
+    pub links : Vec<Vec<(f32, f32)>>, //Tiles of the groups reacting at each link of the chain, where the elements will be then
+    pub products : Vec<Element>, //What the groups reacting first make, in place
+    pub chain : u32,
+    pub score : u64,
+}
+
+#[derive(Clone)]
 pub struct ElementArray
 {
     grid_size : GridSize,
@@ -48,23 +84,53 @@ pub struct ElementArray
     //current pair in control
     pub pair_1 : Tile,
     pub pair_2 : Tile,
-    //next pair
-    next_1 : Tile,
-    next_2 : Tile,
+    //upcoming pairs, the next one first
+    next_pairs : VecDeque<(Tile, Tile)>,
+    //pair put aside, swapped back in on demand
+    hold_1 : Tile,
+    hold_2 : Tile,
+    hold_used : bool, //Only once per drop
 
     unlocks : Vec<ElementType>,
     pub element_data : ElementTypeList,
-    pub texture : Texture2d,
+    rng : SeededRng,
+    spawner : Box<dyn SpawnPolicy>,
+    garbage_rng : SeededRng, //Apart from the pairs, so garbage never changes what comes next
+
+    pub rules : GameRules,
+    score : u64,
+    //Reactions in a row since the last drop
+    chain : u32,
+    longest_chain : u32,
+    pairs_dropped : u32,
+    //Tiles that reacted in the last test_reactions, and what they made
+    last_reacted : Vec<(f32, f32)>,
+    last_products : Vec<(f32, f32, ElementType)>,
+
+    settling_drop : bool, //Garbage only falls once a drop has settled
+    misses : u32, //Drops in a row without a reaction
+    garbage_pending : u32,
+    garbage_received : u32,
+    garbage_cleared : u32,
+    //Garbage for an opponent, if there is one: earned by the drop settling, then ready to send once it has
+    has_opponent : bool,
+    attack : u32,
+    garbage_outgoing : u32,
+    garbage_sent : u32,
 
     effect_time : f32,
 }
 
 impl ElementArray
 {
+    //Center of the grid, in world units; tiles and the windows beside the grid are placed from it
+    pub const ORIGIN : (f32, f32) = (-3.0, -3.0);
+    const SIDE : f32 = 10.75; //From the origin to the next and hold windows
+
     fn reset_array(w: i32, h: i32) -> Vec<Tile>
     {
-        let c = -3.0f32; //Center offset
-        let zero = c - (w as f32); //[0, 0] offset
+        let (ox, oy) = ElementArray::ORIGIN;
+        let zero = (ox - (w as f32), oy - (w as f32)); //[0, 0] offset
         let mut tilevec = Vec::with_capacity((w * h) as usize);
         for px in 0..w
         {
@@ -73,35 +139,69 @@ impl ElementArray
                 //Initialize all tiles to empty
                 // Px * H + Py = Index
                 // Position 0,0 is bottom-left
-                tilevec.push(Tile { x : zero + (2*px) as f32, y : zero + (2*py) as f32, occupant : None });
+                tilevec.push(Tile { x : zero.0 + (2*px) as f32, y : zero.1 + (2*py) as f32, occupant : None });
             }
         }
         tilevec
     }
     fn reset_pairs(w: i32, h: i32) -> (Tile, Tile)
     {
-        let c = -3.0f32; //Center offset
-        let zero = c - (w as f32); //[0, 0] offset
-        (Tile { x : zero + (2*(w / 2 - 1)) as f32, y : zero + (2*(h - 1)) as f32, occupant : None },
-        Tile { x : zero + (2*(w / 2)) as f32, y : zero + (2*(h - 1)) as f32, occupant : None })
+        let (ox, oy) = ElementArray::ORIGIN;
+        let zero = (ox - (w as f32), oy - (w as f32)); //[0, 0] offset
+        (Tile { x : zero.0 + (2*(w / 2 - 1)) as f32, y : zero.1 + (2*(h - 1)) as f32, occupant : None },
+        Tile { x : zero.0 + (2*(w / 2)) as f32, y : zero.1 + (2*(h - 1)) as f32, occupant : None })
+    }
+    fn reset_hold() -> (Tile, Tile)
+    {
+        let c = ElementArray::ORIGIN.0 + ElementArray::SIDE; //Center of hold window, below the next window
+        let y = ElementArray::ORIGIN.1 + 7.5;
+        (Tile { x : c - 2.0, y : y, occupant : None }, //Left tile
+        Tile { x : c, y : y, occupant : None }) //Right tile
+    }
+    //Left and right tiles of an upcoming pair: the first one in the next window,
+    //the ones after it at half size, in a column beside it
+    fn get_next_slot(index : usize, top : bool) -> ((f32, f32), (f32, f32))
+    {
+        let c = ElementArray::ORIGIN.0 + ElementArray::SIDE; //Center of next window, X offset
+        let oy = ElementArray::ORIGIN.1;
+        let (y_next, y_out, y_queue) = (oy + 12.0, oy + 16.0, oy + 15.0);
+        match (index, top)
+        {
+            (0, false) => ((c - 2.0, y_next), (c, y_next)),
+            (0, true) => ((c - 2.0, y_out), (c, y_out)), //Out of the window, making way
+            //Top moves the column up a slot ahead of time, the first one out of view
+            (i, _) =>
+            {
+                let y = y_queue - 1.25 * (i - top as usize) as f32;
+                ((c + 2.25, y), (c + 3.25, y))
+            },
+        }
     }
-    fn reset_next() -> (Tile, Tile)
+    //Where the windows beside the grid are, to clip what is drawn in them
+    //(x, y, width, height) of the next, hold and queue windows
+    pub fn get_side_windows() -> [(f32, f32, f32, f32); 3]
     {
-        let c = 8.0f32; //Center of next window, X offset. +1 for Y offset
-        (Tile { x : c - 1.0, y : c + 1.0, occupant : None }, //Left tile
-        Tile { x : c + 1.0, y : c + 1.0, occupant : None }) //Right tile
+        let (c, y) = (ElementArray::ORIGIN.0 + ElementArray::SIDE, ElementArray::ORIGIN.1);
+        [(c - 2.25, y + 11.25, 4.5, 3.5), (c - 2.25, y + 7.25, 4.5, 3.5), (c + 2.0, y + 9.75, 2.25, 5.0)]
     }
     pub fn set_next_position(&mut self, top : bool)
     {
-        let c = 8.0f32;
-        self.next_1.x = c - 1.0;
-        self.next_2.x = c + 1.0;
-        let y_offset = if top { 5.0 } else { 1.0 };
-        self.next_1.y = c + y_offset;
-        self.next_2.y = c + y_offset;
+        for (i, &mut (ref mut next_1, ref mut next_2)) in self.next_pairs.iter_mut().enumerate()
+        {
+            let ((x1, y1), (x2, y2)) = ElementArray::get_next_slot(i, top);
+            next_1.x = x1;
+            next_1.y = y1;
+            next_2.x = x2;
+            next_2.y = y2;
+        }
+    }
+    //Length of the queue of upcoming pairs
+    fn get_next_count(&self) -> usize
+    {
+        self.rules.next_pairs.min(MAX_NEXT_PAIRS)
     }
 
-    pub fn new(disp : &glium::Display, size: &GridSize) -> ElementArray
+    pub fn new(size: &GridSize) -> ElementArray
     {
         let w = *size as i32;
         //Two extra spots to hold excess elements, in case they react or cause game overs
@@ -109,7 +209,7 @@ impl ElementArray
 
         let tilevec = ElementArray::reset_array(w, h);
         let (p1, p2) = ElementArray::reset_pairs(w, h);
-        let (n1, n2) = ElementArray::reset_next();
+        let (h1, h2) = ElementArray::reset_hold();
         ElementArray
         {
             grid_size : *size,
@@ -121,22 +221,63 @@ impl ElementArray
             guide_rot : GuideRotation::LEFT,
             pair_1 : p1,
             pair_2 : p2,
-            next_1 : n1,
-            next_2 : n2,
-            
-            texture : loader::get_sprite(disp, "Elements.png"),
+            next_pairs : VecDeque::new(),
+            hold_1 : h1,
+            hold_2 : h2,
+            hold_used : false,
 
             unlocks : vec![],
             element_data : ElementTypeList::new(),
+            rng : SeededRng::new(0),
+            spawner : GameRules::new().spawn.create(),
+            garbage_rng : SeededRng::new(ElementArray::get_garbage_seed(0)),
+
+            rules : GameRules::new(),
+            score : 0,
+            chain : 0,
+            longest_chain : 0,
+            pairs_dropped : 0,
+            last_reacted : vec![],
+            last_products : vec![],
+
+            settling_drop : false,
+            misses : 0,
+            garbage_pending : 0,
+            garbage_received : 0,
+            garbage_cleared : 0,
+            has_opponent : false,
+            attack : 0,
+            garbage_outgoing : 0,
+            garbage_sent : 0,
 
             effect_time : 0.0,
         }
     }
+    fn get_garbage_seed(seed: u64) -> u64
+    {
+        !seed
+    }
 
-    pub fn reset(&mut self, size: &GridSize)
+    pub fn reset(&mut self, size: &GridSize, seed: u64)
     {
         //Reset this
         self.effect_time = 0.0;
+        //Same seed, same sequence of elements
+        self.rng = SeededRng::new(seed);
+        self.spawner = self.rules.spawn.create();
+        self.garbage_rng = SeededRng::new(ElementArray::get_garbage_seed(seed));
+        self.score = 0;
+        self.chain = 0;
+        self.longest_chain = 0;
+        self.pairs_dropped = 0;
+        self.settling_drop = false;
+        self.misses = 0;
+        self.garbage_pending = 0;
+        self.garbage_received = 0;
+        self.garbage_cleared = 0;
+        self.attack = 0;
+        self.garbage_outgoing = 0;
+        self.garbage_sent = 0;
 
         self.grid_size = *size;
         self.width = *size as i32;
@@ -146,18 +287,220 @@ impl ElementArray
         let (p1, p2) = ElementArray::reset_pairs(self.width, self.height);
         self.pair_1 = p1;
         self.pair_2 = p2;
-        let (n1, n2) = ElementArray::reset_next();
-        self.next_1 = n1;
-        self.next_2 = n2;
+        self.next_pairs.clear();
+        let (h1, h2) = ElementArray::reset_hold();
+        self.hold_1 = h1;
+        self.hold_2 = h2;
+        self.hold_used = false;
         self.guide_pos = (self.width / 2) - 1;
         self.guide_rot = GuideRotation::LEFT;
 
-        self.unlocks = vec![];
-        //self.unlocks.push(ElementType::ASH);
-        self.unlocks.push(ElementType::AIR);
-        self.unlocks.push(ElementType::WATER);
-        self.unlocks.push(ElementType::EARTH);
-        self.unlocks.push(ElementType::FIRE);
+        self.unlocks = self.element_data.get_starting().clone();
+    }
+
+    pub fn get_seed(&self) -> u64
+    {
+        self.rng.get_seed()
+    }
+    pub fn get_score(&self) -> u64
+    {
+        self.score
+    }
+    pub fn get_chain(&self) -> u32
+    {
+        self.chain
+    }
+    pub fn get_longest_chain(&self) -> u32
+    {
+        self.longest_chain
+    }
+    pub fn get_pairs_dropped(&self) -> u32
+    {
+        self.pairs_dropped
+    }
+    pub fn get_garbage_received(&self) -> u32
+    {
+        self.garbage_received
+    }
+    pub fn get_garbage_cleared(&self) -> u32
+    {
+        self.garbage_cleared
+    }
+    pub fn get_garbage_sent(&self) -> u32
+    {
+        self.garbage_sent
+    }
+    //Drops left before garbage falls in, if the rules call for it
+    pub fn get_garbage_countdown(&self) -> Option<u32>
+    {
+        match self.rules.garbage_after
+        {
+            0 => None,
+            after => Some(after.saturating_sub(self.misses)),
+        }
+    }
+    //Elements unlocked since the start of the game, making a timed game faster
+    pub fn get_speed_level(&self) -> u32
+    {
+        self.unlocks.len().saturating_sub(self.element_data.get_starting().len()) as u32
+    }
+    //Most valuable element spawned or made this game
+    pub fn get_highest_element(&self) -> Option<ElementType>
+    {
+        let mut highest : Option<ElementType> = None;
+        for t in &self.unlocks
+        {
+            if highest.is_none() || self.element_data.get_data(highest.as_ref().unwrap()).get_value() < self.element_data.get_data(t).get_value()
+            {
+                highest = Some(*t);
+            }
+        }
+        highest
+    }
+
+    //Snapshot of the board; the caller fills in what belongs to the game around it
+    pub fn save_state(&self) -> SaveGame
+    {
+        let data = &self.element_data;
+        let get_type = |tile : &Tile| tile.occupant.as_ref().map(|e| data.get_name(e.get_type()).to_owned());
+        SaveGame
+        {
+            grid_size : self.grid_size,
+            mode : self.rules.mode,
+            spawn : self.rules.spawn,
+            spawn_count : self.spawner.get_count(),
+            spawn_memory : self.spawner.get_memory().iter().map(|t| self.element_data.get_name(t).to_owned()).collect(),
+            product_placement : self.rules.product_placement,
+            combo_multipliers : self.rules.combo_multipliers.clone(),
+            garbage_after : self.rules.garbage_after,
+            garbage_amount : self.rules.garbage_amount,
+            seed : self.rng.get_seed(),
+            rng_state : self.rng.get_state(),
+            time : 0.0,
+            settling : false,
+            score : self.score,
+            chain : self.chain,
+            longest_chain : self.longest_chain,
+            pairs_dropped : self.pairs_dropped,
+            drop_times : self.rules.drop_times.clone(),
+            drop_time_left : None,
+            garbage_rng_state : Some(self.garbage_rng.get_state()),
+            misses : self.misses,
+            garbage_pending : self.garbage_pending,
+            garbage_received : self.garbage_received,
+            garbage_cleared : self.garbage_cleared,
+            guide_pos : self.guide_pos,
+            guide_rot : self.guide_rot,
+            unlocks : self.unlocks.iter().map(|t| data.get_name(t).to_owned()).collect(),
+            pair : [get_type(&self.pair_1), get_type(&self.pair_2)],
+            next : self.next_pairs.iter().map(|&(ref n1, ref n2)| [get_type(n1), get_type(n2)]).collect(),
+            hold : [get_type(&self.hold_1), get_type(&self.hold_2)],
+            hold_used : self.hold_used,
+            tiles : self.array.iter().map(get_type).collect(),
+            recent : (0..self.array.len()).filter(|i| self.array[*i].occupant.as_ref().map(|e| e.is_recent()).unwrap_or(false)).collect(),
+            steps : vec![],
+        }
+    }
+    pub fn load_state(&mut self, save : &SaveGame) -> Result<(), String>
+    {
+        self.reset(&save.grid_size, save.seed);
+        if save.tiles.len() != self.array.len()
+        {
+            return Err(format!("Saved board has {} tiles, expected {}", save.tiles.len(), self.array.len()))
+        }
+        self.rules.mode = save.mode;
+        self.rules.spawn = save.spawn;
+        self.rules.product_placement = save.product_placement;
+        self.rules.combo_multipliers = save.combo_multipliers.clone();
+        self.rules.garbage_after = save.garbage_after;
+        self.rules.garbage_amount = save.garbage_amount;
+        //No more than the mode shows, whatever the file says
+        self.rules.next_pairs = save.next.len().min(GameRules::for_mode(save.mode).next_pairs);
+        self.rules.drop_times = save.drop_times.clone();
+        self.spawner = save.spawn.create();
+        self.rng = SeededRng::from_state(save.seed, save.rng_state);
+        self.score = save.score;
+        self.chain = save.chain;
+        self.longest_chain = save.longest_chain;
+        self.pairs_dropped = save.pairs_dropped;
+        if let Some(state) = save.garbage_rng_state
+        {
+            self.garbage_rng = SeededRng::from_state(ElementArray::get_garbage_seed(save.seed), state);
+        }
+        //A drop still settling brings its garbage once it is done
+        self.settling_drop = save.settling;
+        self.misses = save.misses;
+        self.garbage_pending = save.garbage_pending;
+        self.garbage_received = save.garbage_received;
+        self.garbage_cleared = save.garbage_cleared;
+        let data = &self.element_data;
+        let find = |name : &String| data.from_name(name).ok_or_else(|| format!("Saved game uses unknown element \"{}\"", name));
+        let find_all = |names : &[Option<String>]| names.iter()
+            .map(|n| match *n { Some(ref n) => find(n).map(Some), None => Ok(None) })
+            .collect::<Result<Vec<Option<ElementType>>, String>>();
+        self.unlocks = save.unlocks.iter().map(&find).collect::<Result<Vec<ElementType>, String>>()?;
+        //Spawn policies count on something to draw
+        if !self.unlocks.iter().any(|t| data.get_data(t).get_weight() > 0)
+        {
+            return Err("Saved game has no element left to spawn".to_owned())
+        }
+        self.spawner.set_memory(save.spawn_memory.iter().map(&find).collect::<Result<Vec<ElementType>, String>>()?);
+        self.spawner.set_count(save.spawn_count);
+        let tiles = find_all(&save.tiles)?;
+        let pairs = find_all(&[save.pair[0].clone(), save.pair[1].clone(), save.hold[0].clone(), save.hold[1].clone()])?;
+        let next = find_all(&save.next.concat())?;
+
+        //Everything appears right in place
+        for (tile, t) in self.array.iter_mut().zip(tiles.iter())
+        {
+            tile.occupant = t.map(|t| Element::new(tile.x, tile.y, t));
+        }
+        for i in &save.recent
+        {
+            if let Some(element) = self.array[*i].occupant.as_mut()
+            {
+                element.set_recent(true);
+            }
+        }
+        self.guide_pos = save.guide_pos;
+        self.guide_rot = save.guide_rot;
+        self.move_pair(0); //Keeps the guide within bounds
+        for (tile, t) in [&mut self.pair_1, &mut self.pair_2, &mut self.hold_1, &mut self.hold_2].iter_mut()
+            .zip(pairs.iter())
+        {
+            tile.occupant = t.map(|t| Element::new(tile.x, tile.y, t));
+        }
+        for (i, types) in next.chunks(2).take(self.rules.next_pairs).enumerate()
+        {
+            let ((x1, y1), (x2, y2)) = ElementArray::get_next_slot(i, false);
+            self.next_pairs.push_back((Tile { x : x1, y : y1, occupant : types[0].map(|t| Element::new(x1, y1, t)) },
+                                       Tile { x : x2, y : y2, occupant : types[1].map(|t| Element::new(x2, y2, t)) }));
+        }
+        self.hold_used = save.hold_used;
+        Ok(())
+    }
+
+    //Board and pairs of a puzzle, in place of a new game's
+    pub fn load_puzzle(&mut self, puzzle : &Puzzle)
+    {
+        self.reset(&puzzle.grid_size, 0);
+        for (tile, t) in self.array.iter_mut().zip(puzzle.tiles.iter())
+        {
+            tile.occupant = t.map(|t| Element::new(tile.x, tile.y, t));
+        }
+        let sequence = puzzle.pairs.iter().flat_map(|&(a, b)| vec![a, b]).collect();
+        self.spawner = Box::new(FixedSpawn::new(sequence));
+        self.get_next_pair(false);
+    }
+    //Whether the last reactions made an element
+    pub fn has_made(&self, t : &ElementType) -> bool
+    {
+        self.last_products.iter().any(|&(_, _, p)| p == *t)
+    }
+    //Elements of a type within the grid
+    pub fn count_elements(&self, t : &ElementType) -> usize
+    {
+        self.array.iter().filter(|tile| tile.occupant.as_ref().map(|e| e.get_type() == t).unwrap_or(false)).count()
     }
 
     //Shortcut to test array positions with X and Y
@@ -194,41 +537,124 @@ impl ElementArray
         }
     }
 
-    //Get the VertexBuffer for drawing the elements within
-    pub fn get_buffer(&self, disp : &glium::Display) -> VertexBuffer<TextureVertex>
+    //Tile under a point of the screen, the rows above the grid included
+    pub fn get_tile_at(&self, x : f32, y : f32) -> Option<(i32, i32)>
     {
-        let mut texvec = vec![];
+        let (ox, oy) = ElementArray::ORIGIN;
+        let zero = (ox - (self.width as f32), oy - (self.width as f32)); //[0, 0] offset
+        let (px, py) = (((x - zero.0) / 2.0).floor() as i32, ((y - zero.1) / 2.0).floor() as i32);
+        self.array_at(px, py).map(|_| (px, py))
+    }
+
+    //Elements within the grid and the pair in control, for drawing
+    pub fn get_elements(&self) -> Vec<&Element>
+    {
+        let mut elemvec = vec![];
         for tile in &self.array
         {
             if tile.occupant.is_some()
             {
-                texvec.extend(tile.occupant.as_ref().unwrap().get_vertices(&self.element_data));
+                elemvec.push(tile.occupant.as_ref().unwrap());
             }
         }
 
         if self.pair_1.occupant.is_some()
         {
-            texvec.extend(self.pair_1.occupant.as_ref().unwrap().get_vertices(&self.element_data));
+            elemvec.push(self.pair_1.occupant.as_ref().unwrap());
         }
         if self.pair_2.occupant.is_some()
         {
-            texvec.extend(self.pair_2.occupant.as_ref().unwrap().get_vertices(&self.element_data));
+            elemvec.push(self.pair_2.occupant.as_ref().unwrap());
         }
-        VertexBuffer::new(disp, &texvec).unwrap()
+        elemvec
     }
-    //Get the VertexBuffer for the next pairs (needed because of second scissor)
-    pub fn get_next_buffer(&self, disp : &glium::Display) -> VertexBuffer<TextureVertex>
+    //Plays the drop out on a copy, down to the end of its chain
+    pub fn preview_drop(&self) -> Option<DropPreview>
     {
-        let mut texvec = vec![];
-        if self.next_1.occupant.is_some()
+        if self.pair_1.occupant.is_none() || self.pair_2.occupant.is_none()
+        {
+            return None
+        }
+        let mut copy = self.clone();
+        copy.drop_pair();
+        copy.make_fall();
+        let mut preview = DropPreview { links : vec![], products : vec![], chain : 0, score : 0 };
+        while copy.test_reactions()
         {
-            texvec.extend(self.next_1.occupant.as_ref().unwrap().get_vertices(&self.element_data));
+            preview.links.push(copy.last_reacted.clone());
+            if copy.chain == 1
+            {
+                preview.products = copy.last_products.iter().map(|&(x, y, t)| Element::new(x, y, t)).collect();
+            }
+            copy.make_fall();
         }
-        if self.next_2.occupant.is_some()
+        preview.chain = copy.chain;
+        preview.score = copy.score - self.score;
+        Some(preview)
+    }
+    //Where the pair in control would come to rest if dropped now, at the positions of those tiles
+    //Nothing for an element that would stay above the grid
+    pub fn get_ghost_elements(&self) -> Vec<Element>
+    {
+        let (pair_1, pair_2) = match (self.pair_1.occupant.as_ref(), self.pair_2.occupant.as_ref())
+        {
+            (Some(p1), Some(p2)) => (*p1.get_type(), *p2.get_type()),
+            _ => return vec![],
+        };
+        let column_height = |px : i32| (0..self.height).filter(|py| self.array_at(px, *py).unwrap().occupant.is_some()).count() as i32;
+        let x1 = self.guide_pos;
+        let landing = match self.guide_rot
         {
-            texvec.extend(self.next_2.occupant.as_ref().unwrap().get_vertices(&self.element_data));
+            GuideRotation::LEFT => vec![(x1, column_height(x1), pair_1), (x1 + 1, column_height(x1 + 1), pair_2)],
+            GuideRotation::RIGHT => vec![(x1, column_height(x1), pair_1), (x1 - 1, column_height(x1 - 1), pair_2)],
+            //Same column, the lower one lands first
+            GuideRotation::UP => vec![(x1, column_height(x1), pair_2), (x1, column_height(x1) + 1, pair_1)],
+            GuideRotation::DOWN => vec![(x1, column_height(x1), pair_1), (x1, column_height(x1) + 1, pair_2)],
+        };
+        landing.into_iter()
+            .filter_map(|(px, py, t)| self.array_at(px, py).map(|tile| Element::new(tile.x, tile.y, t)))
+            .collect()
+    }
+    //Elements of the next pair (drawn separately because of second scissor)
+    pub fn get_next_elements(&self) -> Vec<&Element>
+    {
+        let mut elemvec = vec![];
+        if let Some(&(ref next_1, ref next_2)) = self.next_pairs.front()
+        {
+            elemvec.extend(next_1.occupant.as_ref());
+            elemvec.extend(next_2.occupant.as_ref());
         }
-        VertexBuffer::new(disp, &texvec).unwrap()
+        elemvec
+    }
+    //Elements of the pairs queued after the next one, drawn at half size in their own column
+    pub fn get_queue_elements(&self) -> Vec<&Element>
+    {
+        let mut elemvec = vec![];
+        for &(ref next_1, ref next_2) in self.next_pairs.iter().skip(1)
+        {
+            elemvec.extend(next_1.occupant.as_ref());
+            elemvec.extend(next_2.occupant.as_ref());
+        }
+        elemvec
+    }
+    //Elements of the held pair (drawn separately because of their own scissor)
+    pub fn get_hold_elements(&self) -> Vec<&Element>
+    {
+        let mut elemvec = vec![];
+        if self.hold_1.occupant.is_some()
+        {
+            elemvec.push(self.hold_1.occupant.as_ref().unwrap());
+        }
+        if self.hold_2.occupant.is_some()
+        {
+            elemvec.push(self.hold_2.occupant.as_ref().unwrap());
+        }
+        elemvec
+    }
+    //Whether the pair in control can still be held before it drops
+    pub fn can_hold(&self) -> bool
+    {
+        !self.hold_used
     }
 
     pub fn move_elements(&mut self, dt : f32) -> bool
@@ -254,14 +680,26 @@ impl ElementArray
             let reached = self.pair_2.occupant.as_mut().unwrap().move_to(self.pair_2.x, self.pair_2.y, dt, true);
             done = done && reached;
         }
-        if self.next_1.occupant.is_some()
+        for &mut (ref mut next_1, ref mut next_2) in self.next_pairs.iter_mut()
         {
-            let reached = self.next_1.occupant.as_mut().unwrap().move_to(self.next_1.x, self.next_1.y, dt, true);
+            for tile in [next_1, next_2].iter_mut()
+            {
+                if tile.occupant.is_some()
+                {
+                    let (x, y) = (tile.x, tile.y);
+                    let reached = tile.occupant.as_mut().unwrap().move_to(x, y, dt, true);
+                    done = done && reached;
+                }
+            }
+        }
+        if self.hold_1.occupant.is_some()
+        {
+            let reached = self.hold_1.occupant.as_mut().unwrap().move_to(self.hold_1.x, self.hold_1.y, dt, true);
             done = done && reached;
         }
-        if self.next_2.occupant.is_some()
+        if self.hold_2.occupant.is_some()
         {
-            let reached = self.next_2.occupant.as_mut().unwrap().move_to(self.next_2.x, self.next_2.y, dt, true);
+            let reached = self.hold_2.occupant.as_mut().unwrap().move_to(self.hold_2.x, self.hold_2.y, dt, true);
             done = done && reached;
         }
         done
@@ -284,12 +722,32 @@ impl ElementArray
             GuideRotation::DOWN => (self.height - 2, self.height - 1),
             _ => (self.height - 1, self.height - 1), //Left, Right: Same altitude, because of different targets!
         };
+        //Only this drop counts as recent now
+        for tile in self.array.iter_mut()
+        {
+            if let Some(element) = tile.occupant.as_mut()
+            {
+                element.set_recent(false);
+            }
+        }
+        for tile in [&mut self.pair_1, &mut self.pair_2].iter_mut()
+        {
+            if let Some(element) = tile.occupant.as_mut()
+            {
+                element.set_recent(true);
+            }
+        }
         mem::swap(&mut self.array[(x1 * self.height + y1) as usize].occupant, &mut self.pair_1.occupant);
         mem::swap(&mut self.array[(x2 * self.height + y2) as usize].occupant, &mut self.pair_2.occupant);
         //Should never put elements back into pair, cause top layers should be cleared when in play.
         //Just in case, tho...
         self.pair_1.occupant = None;
         self.pair_2.occupant = None;
+        //A new chain starts with this drop
+        self.chain = 0;
+        self.pairs_dropped += 1;
+        self.settling_drop = true;
+        self.hold_used = false;
         //Set back to middle positions
         self.guide_pos = (self.width / 2) - 1;
         self.guide_rot = GuideRotation::LEFT;
@@ -334,45 +792,80 @@ impl ElementArray
             GuideRotation::LEFT => ( 0, self.width - 2 ),
             _ => ( 0, self.width - 1 ),
         };
-        self.guide_pos += dx;
+        self.guide_pos = self.guide_pos.saturating_add(dx); //Replay steps can hold any number
         if self.guide_pos < min { self.guide_pos = min; }
         if self.guide_pos > max { self.guide_pos = max; }
         self.set_pair_positions();
     }
+    //Puts the pair in control aside, bringing back the held one or else the next pair
+    //Returns false once already done since the last drop
+    pub fn hold_pair(&mut self) -> bool
+    {
+        if self.hold_used || self.pair_1.occupant.is_none() || self.pair_2.occupant.is_none()
+        {
+            return false
+        }
+        if self.hold_1.occupant.is_none() && self.next_pairs.is_empty() && self.spawner.is_exhausted()
+        {
+            return false //Nothing would come in its place
+        }
+        mem::swap(&mut self.pair_1.occupant, &mut self.hold_1.occupant);
+        mem::swap(&mut self.pair_2.occupant, &mut self.hold_2.occupant);
+        if self.pair_1.occupant.is_none()
+        {
+            //Nothing was held yet
+            self.get_next_pair(true);
+        }
+        else
+        {
+            self.set_pair_spawn_positions();
+        }
+        //Rise into the hold window, like the next pair does
+        for tile in [&mut self.hold_1, &mut self.hold_2].iter_mut()
+        {
+            let (x, y) = (tile.x, tile.y - 4.0);
+            if let Some(element) = tile.occupant.as_mut()
+            {
+                element.set_pos(x, y);
+            }
+        }
+        self.hold_used = true;
+        true
+    }
     pub fn set_pair_positions(&mut self)
     {
-        let c = -3.0f32; //Center offset
-        let zero = c - (self.width as f32); //[0, 0] offset
+        let (ox, oy) = ElementArray::ORIGIN;
+        let zero = (ox - (self.width as f32), oy - (self.width as f32)); //[0, 0] offset
 
         match self.guide_rot
         {
             GuideRotation::UP =>
             {
-                self.pair_1.x = zero + (self.guide_pos as f32 * 2.0);
-                self.pair_1.y = zero + (((self.height - 1) as f32) * 2.0 + 1.0);
-                self.pair_2.x = zero + (self.guide_pos as f32 * 2.0);
-                self.pair_2.y = zero + (((self.height - 1) as f32) * 2.0 - 1.0);
+                self.pair_1.x = zero.0 + (self.guide_pos as f32 * 2.0);
+                self.pair_1.y = zero.1 + (((self.height - 1) as f32) * 2.0 + 1.0);
+                self.pair_2.x = zero.0 + (self.guide_pos as f32 * 2.0);
+                self.pair_2.y = zero.1 + (((self.height - 1) as f32) * 2.0 - 1.0);
             },
             GuideRotation::RIGHT =>
             {
-                self.pair_1.x = zero + (self.guide_pos as f32 * 2.0);
-                self.pair_1.y = zero + (((self.height - 1) as f32) * 2.0);
-                self.pair_2.x = zero + ((self.guide_pos - 1) as f32 * 2.0);
-                self.pair_2.y = zero + (((self.height - 1) as f32) * 2.0);
+                self.pair_1.x = zero.0 + (self.guide_pos as f32 * 2.0);
+                self.pair_1.y = zero.1 + (((self.height - 1) as f32) * 2.0);
+                self.pair_2.x = zero.0 + ((self.guide_pos - 1) as f32 * 2.0);
+                self.pair_2.y = zero.1 + (((self.height - 1) as f32) * 2.0);
             },
             GuideRotation::DOWN =>
             {
-                self.pair_1.x = zero + (self.guide_pos as f32 * 2.0);
-                self.pair_1.y = zero + (((self.height - 1) as f32) * 2.0 - 1.0);
-                self.pair_2.x = zero + (self.guide_pos as f32 * 2.0);
-                self.pair_2.y = zero + (((self.height - 1) as f32) * 2.0 + 1.0);
+                self.pair_1.x = zero.0 + (self.guide_pos as f32 * 2.0);
+                self.pair_1.y = zero.1 + (((self.height - 1) as f32) * 2.0 - 1.0);
+                self.pair_2.x = zero.0 + (self.guide_pos as f32 * 2.0);
+                self.pair_2.y = zero.1 + (((self.height - 1) as f32) * 2.0 + 1.0);
             },
             GuideRotation::LEFT =>
             {
-                self.pair_1.x = zero + ((self.guide_pos as f32) * 2.0);
-                self.pair_1.y = zero + (((self.height - 1) as f32) * 2.0);
-                self.pair_2.x = zero + ((self.guide_pos + 1) as f32 * 2.0);
-                self.pair_2.y = zero + (((self.height - 1) as f32) * 2.0);
+                self.pair_1.x = zero.0 + ((self.guide_pos as f32) * 2.0);
+                self.pair_1.y = zero.1 + (((self.height - 1) as f32) * 2.0);
+                self.pair_2.x = zero.0 + ((self.guide_pos + 1) as f32 * 2.0);
+                self.pair_2.y = zero.1 + (((self.height - 1) as f32) * 2.0);
             },
         };
     }
@@ -405,6 +898,84 @@ impl ElementArray
         }
     }
 
+    //GARBAGE
+    //Queued to fall in once the current drop settles
+    pub fn add_garbage(&mut self, count : u32)
+    {
+        self.garbage_pending += count;
+    }
+    //Kept from one game to the next: only then do reactions earn garbage to send
+    pub fn set_opponent(&mut self, has_opponent : bool)
+    {
+        self.has_opponent = has_opponent;
+    }
+    //Garbage earned by the drops settled since last asked, for an opponent to receive
+    pub fn take_garbage_outgoing(&mut self) -> u32
+    {
+        let outgoing = self.garbage_outgoing;
+        self.garbage_outgoing = 0;
+        self.garbage_sent += outgoing;
+        outgoing
+    }
+    //Garbage a reacting group earns: one per element past three, and one per link of the chain before its own
+    fn get_attack(group_size : usize, chain : u32) -> u32
+    {
+        group_size.saturating_sub(3) as u32 + chain.saturating_sub(1)
+    }
+    //Once a drop has settled without reacting: counts the miss, then drops whatever garbage is due
+    //Returns true if anything fell in, so it can settle as well
+    pub fn drop_garbage(&mut self) -> bool
+    {
+        if !self.settling_drop
+        {
+            return false
+        }
+        self.settling_drop = false;
+        //What the drop earned cancels garbage on its way first, the rest goes out
+        let cancelled = self.attack.min(self.garbage_pending);
+        self.garbage_pending -= cancelled;
+        self.garbage_outgoing += self.attack - cancelled;
+        self.attack = 0;
+        if self.chain > 0
+        {
+            self.misses = 0;
+        }
+        else if self.rules.garbage_after > 0
+        {
+            self.misses += 1;
+            if self.misses >= self.rules.garbage_after
+            {
+                self.misses = 0;
+                self.garbage_pending += self.rules.garbage_amount;
+            }
+        }
+
+        let garbage = match self.element_data.get_garbage()
+        {
+            Some(t) => t,
+            None => { self.garbage_pending = 0; return false },
+        };
+        let mut dropped = 0;
+        while self.garbage_pending > 0
+        {
+            //Random columns that still have room at the top
+            let open : Vec<i32> = (0..self.width).filter(|px| self.array_at(*px, self.height - 1).unwrap().occupant.is_none()).collect();
+            if open.is_empty()
+            {
+                break
+            }
+            let px = open[self.garbage_rng.below(open.len() as u32) as usize];
+            let tile = self.array_at_mut(px, self.height - 1).unwrap();
+            tile.occupant = Some(Element::new(tile.x, tile.y + 4.0, garbage));
+            self.make_fall();
+            self.garbage_pending -= 1;
+            dropped += 1;
+        }
+        //Whatever found no room stays pending, for the next drop
+        self.garbage_received += dropped;
+        dropped > 0
+    }
+
     //CHECKING
     pub fn test_above(&self) -> bool
     {
@@ -426,19 +997,50 @@ impl ElementArray
     pub fn get_next_pair(&mut self, from_next : bool)
     {
 
-        if from_next // we steal the ones ready in the next pair
+        if from_next && !self.next_pairs.is_empty() // we steal the ones ready at the head of the queue
         {
-            mem::swap(&mut self.pair_1.occupant, &mut self.next_1.occupant);
-            mem::swap(&mut self.pair_2.occupant, &mut self.next_2.occupant);
+            let (mut next_1, mut next_2) = self.next_pairs.pop_front().unwrap();
+            mem::swap(&mut self.pair_1.occupant, &mut next_1.occupant);
+            mem::swap(&mut self.pair_2.occupant, &mut next_2.occupant);
         }
-        else // we make new ones, we just started a game
+        else if !self.spawner.is_exhausted() // we make new ones, we just started a game or nothing is queued
         {
-            let t_1 = self.element_data.get_element(&self.unlocks);
+            let t_1 = self.spawner.next(&self.unlocks, &self.element_data, &mut self.rng);
             self.pair_1.occupant = Some(Element::new(0.0, 0.0, t_1));
-            let t_2 = self.element_data.get_element(&self.unlocks);
+            let t_2 = self.spawner.next(&self.unlocks, &self.element_data, &mut self.rng);
             self.pair_2.occupant = Some(Element::new(0.0, 0.0, t_2));
         }
-        //move into position
+        self.set_pair_spawn_positions();
+        //Spawn new elements at the end of the queue
+        let kept = self.next_pairs.len();
+        while self.next_pairs.len() < self.get_next_count() && !self.spawner.is_exhausted()
+        {
+            let t1 = self.spawner.next(&self.unlocks, &self.element_data, &mut self.rng);
+            let t2 = self.spawner.next(&self.unlocks, &self.element_data, &mut self.rng);
+            self.next_pairs.push_back((Tile { x : 0.0, y : 0.0, occupant : Some(Element::new(0.0, 0.0, t1)) },
+                                       Tile { x : 0.0, y : 0.0, occupant : Some(Element::new(0.0, 0.0, t2)) }));
+        }
+        self.set_next_position(false);
+        //New ones, and the one moving over to the next window, come up from below
+        for (i, &mut (ref mut next_1, ref mut next_2)) in self.next_pairs.iter_mut().enumerate()
+        {
+            if i == 0 || i >= kept
+            {
+                for tile in [next_1, next_2].iter_mut()
+                {
+                    let (x, y) = (tile.x, tile.y - 4.0);
+                    if let Some(element) = tile.occupant.as_mut()
+                    {
+                        element.set_pos(x, y);
+                    }
+                }
+            }
+        }
+    }
+
+    //Pair in control comes down from above its tiles
+    fn set_pair_spawn_positions(&mut self)
+    {
         if self.pair_1.occupant.is_some()
         {
             let xp1 = self.pair_1.x;
@@ -451,23 +1053,17 @@ impl ElementArray
             let yp2 = self.pair_2.y + 5.0;
             self.pair_2.occupant.as_mut().unwrap().set_pos(xp2, yp2);
         }
-        //Spawn new elements
-        let t1 = self.element_data.get_element(&self.unlocks);
-        let x1 = self.next_1.x;
-        let y1 = self.next_1.y - 4.0;
-        self.next_1.occupant = Some(Element::new(x1, y1, t1));
-
-        let t2 = self.element_data.get_element(&self.unlocks);
-        let x2 = self.next_2.x;
-        let y2 = self.next_2.y - 4.0;
-        self.next_2.occupant = Some(Element::new(x2, y2, t2));
     }
 
     //REACTING
     pub fn test_reactions(&mut self) -> bool
     {
         let mut reacts = false;
-        let mut products : Vec<(Coord, ElementType)> = vec![];
+        let mut products : Vec<(Coord, ElementType, bool)> = vec![];
+        //Every group reacting in this pass is the same link of the chain
+        let multiplier = self.rules.get_combo_multiplier(self.chain + 1) as u64;
+        self.last_reacted.clear();
+        self.last_products.clear();
         for py in 0..self.height
         {
             for px in 0..self.width
@@ -499,32 +1095,121 @@ impl ElementArray
                             self.neighbor_reaction_test(Coord{x:tx, y:ty-1}, t1, &mut to_react, &mut to_test, &mut reagents);
                         }
                     }
-                    if to_react.len() >= 3
+                    //Group sizes are up to the element definitions and recipes
+                    let prod = self.element_data.get_product(&reagents);
+                    if prod.is_some()
                     {
-                        let prod = self.element_data.get_product(&reagents);
-                        if prod.is_some()
+                        reacts = true;
+                        //Reagents not making the product stay where they are
+                        let mut to_react : Vec<Coord> = to_react.into_iter().zip(reagents.iter())
+                            .filter(|&(_, r)| self.element_data.is_consumed(r, prod.as_ref().unwrap(), &reagents))
+                            .map(|(c, _)| c).collect();
+                        self.clear_garbage_around(&to_react);
+                        let at = self.get_product_position(&to_react);
+                        for coord in &to_react
+                        {
+                            let tile = self.array_at(coord.x, coord.y).unwrap();
+                            self.last_reacted.push((tile.x, tile.y));
+                        }
+                        self.score += multiplier * self.get_reaction_score(prod.as_ref().unwrap(), to_react.len());
+                        if self.has_opponent
                         {
-                            reacts = true;
-                            while to_react.len() > 0
-                            {
-                                let coord = to_react.pop().unwrap();
-                                self.array_at_mut(coord.x, coord.y).unwrap().occupant = None;
-                            }
-                            products.push((Coord{x:px, y:py}, prod.unwrap()));
+                            self.attack += ElementArray::get_attack(to_react.len(), self.chain + 1);
                         }
+                        while to_react.len() > 0
+                        {
+                            let coord = to_react.pop().unwrap();
+                            self.array_at_mut(coord.x, coord.y).unwrap().occupant = None;
+                        }
+                        products.push((at, prod.unwrap(), self.element_data.is_catalyzed(&reagents)));
                     }
                 }
             }
         }
         while products.len() > 0
         {
-            let (coord, prod) = products.pop().unwrap();
+            let (coord, prod, catalyzed) = products.pop().unwrap();
             if !self.unlocks.contains(&prod) {self.unlocks.push(prod)}
+            for t in self.element_data.get_unlocked_by(&prod)
+            {
+                if !self.unlocks.contains(&t) {self.unlocks.push(t)}
+            }
+            let (x, y) = { let tile = self.array_at(coord.x, coord.y).unwrap(); (tile.x, tile.y) };
+            self.last_products.push((x, y, prod));
             let tile = self.array_at_mut(coord.x, coord.y).unwrap();
-            tile.occupant = Some(Element::new(tile.x, tile.y, prod));
+            let mut product = Element::new(tile.x, tile.y, prod);
+            //Chains carry on from the products
+            product.set_recent(true);
+            if catalyzed
+            {
+                product.set_glow(1.0);
+            }
+            tile.occupant = Some(product);
+        }
+        if reacts
+        {
+            self.chain += 1;
+            self.longest_chain = self.longest_chain.max(self.chain);
         }
         return reacts
     }
+    //Where the rules want the product of a group, picked among its cells
+    fn get_product_position(&self, group : &Vec<Coord>) -> Coord
+    {
+        //Lowest, then leftmost, of a set of cells
+        fn lowest<'c, I : Iterator<Item=&'c Coord>>(cells : I) -> Option<Coord>
+        {
+            cells.min_by_key(|c| (c.y, c.x)).map(|c| Coord{x:c.x, y:c.y})
+        }
+        let fallback = lowest(group.iter()).unwrap();
+        match self.rules.product_placement
+        {
+            ProductPlacement::LOWEST => fallback,
+            ProductPlacement::LAST_DROPPED =>
+            {
+                let recent = group.iter().filter(|c| self.array_at(c.x, c.y).unwrap().occupant.as_ref().map(|e| e.is_recent()).unwrap_or(false));
+                lowest(recent).unwrap_or(fallback)
+            },
+            ProductPlacement::CENTROID =>
+            {
+                //Scaled by the group size, so the middle of the group stays in integers
+                let n = group.len() as i32;
+                let (sx, sy) = group.iter().fold((0, 0), |(sx, sy), c| (sx + c.x, sy + c.y));
+                let distance = |c : &&Coord| (n * c.x - sx).pow(2) + (n * c.y - sy).pow(2);
+                let closest = group.iter().map(|c| distance(&c)).min().unwrap();
+                let candidates = group.iter().filter(|c| distance(c) == closest);
+                lowest(candidates).unwrap_or(fallback)
+            },
+        }
+    }
+    //Garbage only goes away when something reacts next to it
+    fn clear_garbage_around(&mut self, group : &Vec<Coord>)
+    {
+        let garbage = match self.element_data.get_garbage()
+        {
+            Some(t) => t,
+            None => return,
+        };
+        for coord in group
+        {
+            for &(dx, dy) in &[(-1, 0), (1, 0), (0, 1), (0, -1)]
+            {
+                if let Some(tile) = self.array_at_mut(coord.x + dx, coord.y + dy)
+                {
+                    if tile.occupant.as_ref().map(|e| *e.get_type() == garbage).unwrap_or(false)
+                    {
+                        tile.occupant = None;
+                        self.garbage_cleared += 1;
+                    }
+                }
+            }
+        }
+    }
+    //Points for a reaction: the product's value for every element that merged into it
+    fn get_reaction_score(&self, product:&ElementType, group_size:usize) -> u64
+    {
+        self.element_data.get_data(product).get_value() as u64 * group_size as u64
+    }
     fn neighbor_reaction_test(&self, pos:Coord, t:&ElementType, to_react:&mut Vec<Coord>, to_test:&mut Vec<Coord>, reagents:&mut Vec<ElementType>)
     {
         if !to_react.contains(&pos)
@@ -532,7 +1217,7 @@ impl ElementArray
         && self.array_at(pos.x,pos.y).unwrap().occupant.is_some()
         {
             let t2 = self.array_at(pos.x,pos.y).unwrap().occupant.as_ref().unwrap().get_type();
-            if self.element_data.can_react(t, t2)
+            if self.element_data.can_join(t, t2, reagents)
             {
                 to_react.push(pos);
                 to_test.push(pos);
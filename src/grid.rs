@@ -1,45 +1,72 @@
 extern crate glium;
 
+use std::rc::Rc;
+use std::cell::RefCell;
 use glium::VertexBuffer;
 use glium::texture::Texture2d;
 use vertex::{TextureVertex, Square};
-use traits;
+use drawable;
 use camera::Camera;
 use loader;
-use element_array::ElementArray;
+use text;
+use shaders::{ProgramManager, ShaderProgram};
+use metalchemist::traits;
+use metalchemist::board::{Board, GridSize};
+use metalchemist::element::{Element, ElementTypeList};
+use metalchemist::highscores::{HighScore, HighScores};
+use metalchemist::storage;
+use metalchemist::replay::Replay;
+use metalchemist::rules::{GameMode, ProductPlacement};
+use metalchemist::save::SaveGame;
+use metalchemist::settings::Settings;
+use metalchemist::board::GameState;
+use metalchemist::element_array::{DropPreview, ElementArray};
+use metalchemist::puzzle::{Puzzle, PuzzleGoal};
+use metalchemist::editor::{EditorField, PuzzleEditor};
+use versus::VersusResult;
 
-#[derive(Copy, Clone, Debug)]
-pub enum GridSize 
+//What clicking a line of the editor panel does
+#[derive(Copy, Clone)]
+enum EditorCommand
 {
-    FOUR  = 4,
-    FIVE  = 5, 
-    SIX   = 6, 
-    SEVEN = 7, 
-    EIGHT = 8,
-}
-
-pub enum GameState
-{
-    PLAY,
-    FALLING,
-    REACTING,
-    READYING,
-    GAME_OVER,
+    STEP(EditorField),
+    ADD_PAIR,
+    REMOVE_PAIR,
+    TEST,
+    SAVE,
 }
 
+//Renders and drives a Board
 pub struct Grid<'a>
 {
-    game_state : GameState,
-    time : f32,
+    board : Board,
+    high_scores : Rc<RefCell<HighScores>>, //Shared with the other grids on screen
+    result_recorded : bool,
+    last_rank : Option<usize>, //Place of the last game in its table
+    show_scores : bool,
+    pending_resume : Option<SaveGame>, //Saved game waiting for an answer
+    keep_save : bool, //Saved game never offered this session, left as it is
+    settings : Rc<RefCell<Settings>>,
+    puzzles : Vec<Puzzle>,
+    puzzle_index : usize, //Place in the pack of the puzzle played last
+    editor : Option<PuzzleEditor>, //Puzzle being edited, kept while it is tried out
+    player : Option<usize>, //Side of a versus game, its results are not kept
+    versus_result : Option<VersusResult>,
+    //Preview of the pair in control, worked out again only once it moved
+    preview : Option<DropPreview>,
+    preview_stale : bool,
+    //Where the grid is drawn: moved over from the middle of the screen, and scaled
+    offset : (f32, f32),
+    scale : f32,
 
     disp_ref : &'a glium::Display,
 
-    grid_size : GridSize,
     grid_buffer : VertexBuffer<TextureVertex>, //Main grid area
     top_buffer : VertexBuffer<TextureVertex>, //Above grid area (for dropping elements)
 
-    elements : ElementArray,
-
+    tex_elements : Texture2d,
+    tex_font : Texture2d,
+    tex_blank : Texture2d,
     tex_top  : Texture2d,
     tex_four : Texture2d,
     tex_five : Texture2d,
@@ -48,27 +75,44 @@ pub struct Grid<'a>
     tex_eight: Texture2d,
 
     shader : &'a glium::Program,
+    tint_shader : &'a glium::Program,
 }
 
 impl<'a> Grid<'a>
 {
-    pub fn new(disp : &'a glium::Display, program : &'a glium::Program) -> Grid<'a>
+    pub fn new(disp : &'a glium::Display, programs : &'a ProgramManager,
+               high_scores : Rc<RefCell<HighScores>>, settings : Rc<RefCell<Settings>>) -> Grid<'a>
     {
-        let size = GridSize::SIX;
-        let (main, top) = Grid::get_buffers(disp, size);
+        let board = Board::new();
+        let (main, top) = Grid::get_buffers(disp, board.get_grid_size());
         Grid 
         {
-            game_state : GameState::PLAY,
-            time : 0.0,
+            board : board,
+            high_scores : high_scores,
+            result_recorded : false,
+            last_rank : None,
+            show_scores : false,
+            pending_resume : None,
+            keep_save : false,
+            settings : settings,
+            puzzles : vec![],
+            puzzle_index : 0,
+            editor : None,
+            player : None,
+            versus_result : None,
+            preview : None,
+            preview_stale : true,
+            offset : (0.0, 0.0),
+            scale : 1.0,
 
             disp_ref : disp,
 
-            grid_size : size,
             grid_buffer : main,
             top_buffer : top,
 
-            elements : ElementArray::new(disp, &size),
-
+            tex_elements : loader::get_sprite(disp, "Elements.png"),
+            tex_font : text::get_font(disp),
+            tex_blank : loader::get_blank(disp),
             tex_top  : loader::get_sprite(disp, "Placeholder.png"),
             tex_four : loader::get_sprite(disp, "Placeholder.png"),
             tex_five : loader::get_sprite(disp, "Placeholder.png"),
@@ -76,57 +120,507 @@ impl<'a> Grid<'a>
             tex_seven: loader::get_sprite(disp, "Placeholder.png"),
             tex_eight: loader::get_sprite(disp, "Placeholder.png"),
 
-            shader : program,
+            shader : programs.get_program(ShaderProgram::Basic),
+            tint_shader : programs.get_program(ShaderProgram::Tinted),
         }
     }
 
+    //Everything drawn is scaled, then moved over by (x, y)
+    pub fn set_placement(&mut self, x : f32, y : f32, scale : f32)
+    {
+        self.offset = (x, y);
+        self.scale = scale;
+    }
+    //Side of a versus game, against an opponent from now on
+    pub fn set_player(&mut self, player : usize)
+    {
+        self.player = Some(player);
+        self.board.set_opponent(true);
+    }
+    pub fn set_versus_result(&mut self, result : Option<VersusResult>)
+    {
+        self.versus_result = result;
+    }
+    //Garbage sent from and to the opponent in versus
+    pub fn take_garbage_outgoing(&mut self) -> u32
+    {
+        self.board.take_garbage_outgoing()
+    }
+    pub fn add_garbage(&mut self, count : u32)
+    {
+        self.board.add_garbage(count);
+    }
+    pub fn is_game_over(&self) -> bool
+    {
+        self.board.is_game_over()
+    }
+    pub fn stop(&mut self)
+    {
+        self.board.stop();
+    }
+
+    //The puzzle being edited is resized instead
     pub fn upscale(&mut self)
     {
-        let size = match self.grid_size
+        if self.is_editing()
         {
-            GridSize::FOUR  => GridSize::FIVE,
-            GridSize::FIVE  => GridSize::SIX,
-            GridSize::SIX   => GridSize::SEVEN,
-            GridSize::SEVEN => GridSize::EIGHT,
-            GridSize::EIGHT => GridSize::EIGHT,
-        };
-        self.reset_grid(size)
+            return self.edit(EditorCommand::STEP(EditorField::SIZE), true)
+        }
+        self.board.upscale();
+        self.editor = None;
+        self.refresh_buffers();
     }
     pub fn downscale(&mut self)
     {
-        let size = match self.grid_size
+        if self.is_editing()
         {
-            GridSize::FOUR  => GridSize::FOUR,
-            GridSize::FIVE  => GridSize::FOUR,
-            GridSize::SIX   => GridSize::FIVE,
-            GridSize::SEVEN => GridSize::SIX,
-            GridSize::EIGHT => GridSize::SEVEN,
-        };
-        self.reset_grid(size)
+            return self.edit(EditorCommand::STEP(EditorField::SIZE), false)
+        }
+        self.board.downscale();
+        self.editor = None;
+        self.refresh_buffers();
     }
 
     pub fn reset_grid(&mut self, size: GridSize)
     {
-        //Set new size
-        self.grid_size = size;
-        
-        //Empties grid & resizes it
-        self.elements.reset(&size);
-        //Get Elements to play, too
-        self.elements.get_next_pair(false);
-        
-        //get corresponding vertexes
-        let (main, top) = Grid::get_buffers(self.disp_ref, self.grid_size);
+        self.board.reset(size);
+        self.editor = None;
+        self.refresh_buffers();
+    }
+    //Once a game is over; a solved puzzle moves on to the next one, the last one back to regular games
+    //A puzzle tried out from the editor goes back to it
+    pub fn restart(&mut self)
+    {
+        if self.board.is_game_over() && self.editor.is_some()
+        {
+            return self.show_editor()
+        }
+        if self.board.is_game_over() && self.board.is_puzzle_solved()
+        {
+            if self.puzzle_index + 1 < self.puzzles.len()
+            {
+                let index = self.puzzle_index + 1;
+                self.start_puzzle(index);
+            }
+            else
+            {
+                self.puzzle_index = 0;
+                let size = self.board.get_grid_size();
+                self.reset_grid(size);
+            }
+            return
+        }
+        self.board.restart();
+        self.refresh_buffers();
+    }
+
+    pub fn set_puzzles(&mut self, puzzles : Vec<Puzzle>)
+    {
+        self.puzzles = puzzles;
+        self.puzzle_index = 0;
+    }
+    //Between the puzzle pack, where it was left, and regular games
+    pub fn toggle_puzzles(&mut self)
+    {
+        if self.pending_resume.is_some()
+        {
+            return
+        }
+        if self.board.get_puzzle().is_some()
+        {
+            let size = self.board.get_grid_size();
+            self.reset_grid(size);
+        }
+        else if self.puzzles.is_empty()
+        {
+            storage::report("No puzzles to play");
+        }
+        else
+        {
+            let index = self.puzzle_index;
+            self.start_puzzle(index);
+        }
+    }
+    //Same puzzle from the start, at any time
+    pub fn retry_puzzle(&mut self)
+    {
+        if self.is_editing()
+        {
+            return
+        }
+        if let Some(ref editor) = self.editor
+        {
+            self.board.start_puzzle(editor.get_puzzle());
+            self.preview_stale = true;
+        }
+        else if self.board.get_puzzle().is_some()
+        {
+            let index = self.puzzle_index;
+            self.start_puzzle(index);
+        }
+    }
+    fn start_puzzle(&mut self, index : usize)
+    {
+        self.puzzle_index = index;
+        self.board.start_puzzle(&self.puzzles[index]);
+        self.editor = None;
+        self.refresh_buffers();
+    }
+
+    //Opens the editor on the puzzle in play, or a new one; from a try out, back to editing
+    pub fn toggle_editor(&mut self)
+    {
+        if self.pending_resume.is_some()
+        {
+            return
+        }
+        if self.is_editing()
+        {
+            let size = self.board.get_grid_size();
+            return self.reset_grid(size)
+        }
+        if self.editor.is_none()
+        {
+            self.editor = Some(match self.board.get_puzzle()
+            {
+                Some(puzzle) => PuzzleEditor::new(puzzle.clone()),
+                None =>
+                {
+                    let mut n = self.puzzles.len() + 1;
+                    while self.puzzles.iter().any(|p| p.name == format!("puzzle {}", n))
+                    {
+                        n += 1;
+                    }
+                    PuzzleEditor::blank(&format!("puzzle {}", n), &self.board.get_elements().element_data)
+                },
+            });
+        }
+        self.show_editor();
+    }
+    fn is_editing(&self) -> bool
+    {
+        *self.board.get_state() == GameState::EDITING
+    }
+    fn show_editor(&mut self)
+    {
+        if let Some(ref editor) = self.editor
+        {
+            self.board.edit_puzzle(&editor.get_shown());
+        }
+        self.refresh_buffers();
+    }
+    //Lines of the editor panel, and what clicking them does
+    fn get_editor_lines(&self) -> Vec<(String, Option<EditorCommand>)>
+    {
+        let editor = match self.editor
+        {
+            Some(ref editor) => editor,
+            None => return vec![],
+        };
+        let puzzle = editor.get_puzzle();
+        let data = &self.board.get_elements().element_data;
+        let size = puzzle.grid_size as i32;
+        let (goal, target) = match puzzle.goal
+        {
+            PuzzleGoal::MAKE(t) => ("MAKE", data.get_name(&t).to_uppercase()),
+            PuzzleGoal::CLEAR(t) => ("CLEAR", data.get_name(&t).to_uppercase()),
+            PuzzleGoal::SCORE(points) => ("SCORE", points.to_string()),
+        };
+        let step = |field| Some(EditorCommand::STEP(field));
+        vec![
+            ("EDITOR".to_owned(), None),
+            (puzzle.name.to_uppercase(), None),
+            (format!("SIZE {}X{}", size, size), step(EditorField::SIZE)),
+            (format!("GOAL {}", goal), step(EditorField::GOAL)),
+            (format!(" {}", target), step(EditorField::TARGET)),
+            (format!("DROPS {} OF {}", puzzle.drops, puzzle.pairs.len()), step(EditorField::DROPS)),
+            (format!("PAIR {}/{}", editor.get_pair_index() + 1, puzzle.pairs.len()), step(EditorField::PAIR)),
+            ("ADD PAIR".to_owned(), Some(EditorCommand::ADD_PAIR)),
+            ("REMOVE PAIR".to_owned(), Some(EditorCommand::REMOVE_PAIR)),
+            ("TRY IT".to_owned(), Some(EditorCommand::TEST)),
+            ("SAVE".to_owned(), Some(EditorCommand::SAVE)),
+            ("".to_owned(), None),
+            ("CLICK: NEXT".to_owned(), None),
+            ("RIGHT: BACK".to_owned(), None),
+        ]
+    }
+    //Mouse click at a screen position, forward for the left button; only the editor takes them
+    pub fn click(&mut self, x : f32, y : f32, forward : bool)
+    {
+        if !self.is_editing()
+        {
+            return
+        }
+        //Into the grid's own coordinates
+        let (x, y) = ((x - self.offset.0) / self.scale, (y - self.offset.1) / self.scale);
+        //Tiles and the pair in view, then the lines of the panel
+        if let Some((px, py)) = self.board.get_elements().get_tile_at(x, y)
+        {
+            let data = &self.board.get_elements().element_data;
+            let changed = match self.editor
+            {
+                Some(ref mut editor) => editor.cycle_tile(px, py, forward, data),
+                None => false,
+            };
+            if changed
+            {
+                self.show_editor();
+            }
+            return
+        }
+        let (left, top) = Grid::get_hud_position();
+        let line = ((top + 0.95 - y) / 0.95).floor();
+        if x < left - 0.25 || line < 0.0
+        {
+            return
+        }
+        if let Some(&(_, Some(command))) = self.get_editor_lines().get(line as usize)
+        {
+            self.edit(command, forward);
+        }
+    }
+    fn edit(&mut self, command : EditorCommand, forward : bool)
+    {
+        match command
+        {
+            EditorCommand::TEST =>
+            {
+                if let Some(ref editor) = self.editor
+                {
+                    self.board.start_puzzle(editor.get_puzzle());
+                }
+            },
+            EditorCommand::SAVE => self.save_puzzle(),
+            _ =>
+            {
+                let data = &self.board.get_elements().element_data;
+                if let Some(ref mut editor) = self.editor
+                {
+                    match command
+                    {
+                        EditorCommand::STEP(field) => editor.step(field, forward, data),
+                        EditorCommand::ADD_PAIR => editor.add_pair(),
+                        EditorCommand::REMOVE_PAIR => editor.remove_pair(),
+                        _ => (),
+                    }
+                }
+            },
+        }
+        if self.is_editing()
+        {
+            self.show_editor();
+        }
+        else
+        {
+            self.refresh_buffers();
+        }
+    }
+    //Into the pack, over the puzzle of the same name if there is one
+    fn save_puzzle(&mut self)
+    {
+        let puzzle = match self.editor
+        {
+            Some(ref editor) => editor.get_puzzle().clone(),
+            None => return,
+        };
+        match self.puzzles.iter().position(|p| p.name == puzzle.name)
+        {
+            Some(i) => self.puzzles[i] = puzzle,
+            None => self.puzzles.push(puzzle),
+        }
+        let path = Puzzle::get_default_path();
+        match Puzzle::save_pack(&path, &self.puzzles, &self.board.get_elements().element_data)
+        {
+            Ok(()) => storage::report(&format!("Saved puzzle pack {}", path.display())),
+            Err(e) => storage::report(&format!("Could not save puzzles: {}", e)),
+        }
+    }
+    pub fn set_element_data(&mut self, data : ElementTypeList)
+    {
+        self.board.set_element_data(data);
+    }
+    //Applies from the next game
+    pub fn cycle_placement(&mut self)
+    {
+        let mut rules = self.board.get_next_rules().clone();
+        rules.product_placement = rules.product_placement.next();
+        self.board.set_next_rules(rules);
+    }
+    //Applies from the next game
+    pub fn cycle_mode(&mut self)
+    {
+        let mut rules = self.board.get_next_rules().clone();
+        rules.mode = rules.mode.next();
+        self.board.set_next_rules(rules);
+    }
+    //Applies from the next game
+    pub fn toggle_timer(&mut self)
+    {
+        let mut rules = self.board.get_next_rules().clone();
+        let timed = !rules.is_timed();
+        rules.set_timed(timed);
+        self.board.set_next_rules(rules);
+    }
+    pub fn toggle_ghost(&mut self)
+    {
+        let mut settings = self.settings.borrow_mut();
+        settings.show_ghost = !settings.show_ghost;
+        if let Err(e) = settings.save()
+        {
+            storage::report(&format!("Could not save settings: {}", e));
+        }
+    }
+    pub fn toggle_preview(&mut self)
+    {
+        let mut settings = self.settings.borrow_mut();
+        settings.show_preview = !settings.show_preview;
+        if let Err(e) = settings.save()
+        {
+            storage::report(&format!("Could not save settings: {}", e));
+        }
+    }
+    pub fn toggle_scores(&mut self)
+    {
+        self.show_scores = !self.show_scores;
+    }
+
+    //Saves the inputs of the current game so far, and returns where
+    pub fn save_replay(&self) -> Option<String>
+    {
+        if self.board.get_puzzle().is_some()
+        {
+            storage::report("Puzzles have no replays");
+            return None
+        }
+        let name = format!("replay-{}.txt", storage::get_timestamp());
+        let path = Replay::get_default_dir().join(name);
+        match self.board.get_replay().save(&path)
+        {
+            Ok(()) => Some(path.display().to_string()),
+            Err(e) =>
+            {
+                storage::report(&format!("Could not save replay: {}", e));
+                None
+            },
+        }
+    }
+    pub fn play_replay(&mut self, replay: Replay)
+    {
+        if let Err(e) = self.board.play_replay(replay)
+        {
+            return storage::report(&format!("Could not play replay: {}", e))
+        }
+        self.editor = None;
+        self.refresh_buffers();
+    }
+    //Where the last finished game is kept
+    pub fn get_last_replay_path() -> ::std::path::PathBuf
+    {
+        Replay::get_default_dir().join("last.txt")
+    }
+
+    //Asks whether to pick up a saved game before playing
+    pub fn offer_resume(&mut self, save: SaveGame)
+    {
+        self.pending_resume = Some(save);
+    }
+    //Session started on another game, the saved one waits for the next
+    pub fn keep_saved_game(&mut self)
+    {
+        self.keep_save = true;
+    }
+    pub fn answer_resume(&mut self, accept: bool)
+    {
+        if let Some(save) = self.pending_resume.take()
+        {
+            if !accept
+            {
+                SaveGame::delete(&SaveGame::get_default_path());
+                return
+            }
+            match self.board.resume(&save)
+            {
+                Ok(()) => self.refresh_buffers(),
+                Err(e) => storage::report(&format!("Could not resume: {}", e)),
+            }
+        }
+    }
+    //Keeps the game in progress for next time, on exit
+    pub fn save_game(&self)
+    {
+        if self.pending_resume.is_some() || self.keep_save
+        {
+            return //Still the previous game, untouched
+        }
+        let path = SaveGame::get_default_path();
+        match self.board.save_game()
+        {
+            Some(save) => if let Err(e) = save.save(&path) { storage::report(&format!("Could not save game: {}", e)) },
+            //Only a game played to the end is done with, not one set aside for a replay or a puzzle
+            None if self.board.is_game_over() && !self.board.is_replaying() && self.board.get_puzzle().is_none() =>
+                SaveGame::delete(&path),
+            None => (),
+        }
+    }
+
+    //Keep the finished game in the high score table, and its replay
+    fn record_result(&mut self)
+    {
+        self.result_recorded = true;
+        if self.board.is_replaying() || self.board.get_puzzle().is_some() || self.player.is_some()
+        {
+            return
+        }
+        if let Err(e) = self.board.get_replay().save(&Grid::get_last_replay_path())
+        {
+            storage::report(&format!("Could not save replay: {}", e));
+        }
+        if self.board.get_score() == 0
+        {
+            return
+        }
+        let entry = HighScore
+        {
+            mode : self.board.get_rules().mode,
+            grid_size : self.board.get_grid_size(),
+            score : self.board.get_score(),
+            highest : self.get_highest_name(),
+            date : storage::get_timestamp(),
+            seed : self.board.get_seed(),
+        };
+        let mut high_scores = self.high_scores.borrow_mut();
+        self.last_rank = high_scores.insert(entry);
+        if let Err(e) = high_scores.save()
+        {
+            storage::report(&format!("Could not save high scores: {}", e));
+        }
+    }
+    fn get_highest_name(&self) -> String
+    {
+        match self.board.get_highest_element()
+        {
+            Some(t) => self.board.get_elements().element_data.get_name(&t).to_uppercase(),
+            None => "-".to_owned(),
+        }
+    }
+    pub fn reset_grid_with_seed(&mut self, size: GridSize, seed: u64)
+    {
+        self.board.reset_with_seed(size, seed);
+        self.refresh_buffers();
+    }
+
+    //get vertexes corresponding to the board's size
+    fn refresh_buffers(&mut self)
+    {
+        let (main, top) = Grid::get_buffers(self.disp_ref, self.board.get_grid_size());
         self.grid_buffer = main;
         self.top_buffer = top;
-
-        //Reset to play state
-        self.game_state = GameState::PLAY;
+        self.preview_stale = true; //Board started over one way or another
     }
 
     fn get_buffers(disp: &glium::Display, size: GridSize) -> (VertexBuffer<TextureVertex>,VertexBuffer<TextureVertex>)
     {
-        let c = -3.0f32; //Center offset
+        let (cx, cy) = ElementArray::ORIGIN; //Center offset
         let t = 6.0f32; //Top offset
         let depth = -2.0f32; //Behind most things
         let val = (size as i32) as f32; //scales with the size of the grid
@@ -135,20 +629,20 @@ impl<'a> Grid<'a>
         //Main grid
         let shp = Square
         {
-            top_left:     TextureVertex { position: [c - val, c + h, depth], tex_coords: [ 0.0, 1.0 ] },
-            top_right:    TextureVertex { position: [c + val, c + h, depth], tex_coords: [ 1.0, 1.0 ] },
-            bottom_left:  TextureVertex { position: [c - val, c - h, depth], tex_coords: [ 0.0, 0.0 ] },
-            bottom_right: TextureVertex { position: [c + val, c - h, depth], tex_coords: [ 1.0, 0.0 ] },
+            top_left:     TextureVertex { position: [cx - val, cy + h, depth], tex_coords: [ 0.0, 1.0 ] },
+            top_right:    TextureVertex { position: [cx + val, cy + h, depth], tex_coords: [ 1.0, 1.0 ] },
+            bottom_left:  TextureVertex { position: [cx - val, cy - h, depth], tex_coords: [ 0.0, 0.0 ] },
+            bottom_right: TextureVertex { position: [cx + val, cy - h, depth], tex_coords: [ 1.0, 0.0 ] },
         };
         let main = VertexBuffer::new(disp, &shp.get_vec()).unwrap();
         
         //Above grid
         let shp_top = Square
         {
-            top_left:     TextureVertex { position: [c - val, c + h + t, depth], tex_coords: [ 0.0, 1.0 ] },
-            top_right:    TextureVertex { position: [c + val, c + h + t, depth], tex_coords: [ val, 1.0 ] },
-            bottom_left:  TextureVertex { position: [c - val, c + h, depth], tex_coords: [ 0.0, 0.0 ] },
-            bottom_right: TextureVertex { position: [c + val, c + h, depth], tex_coords: [ val, 0.0 ] },
+            top_left:     TextureVertex { position: [cx - val, cy + h + t, depth], tex_coords: [ 0.0, 1.0 ] },
+            top_right:    TextureVertex { position: [cx + val, cy + h + t, depth], tex_coords: [ val, 1.0 ] },
+            bottom_left:  TextureVertex { position: [cx - val, cy + h, depth], tex_coords: [ 0.0, 0.0 ] },
+            bottom_right: TextureVertex { position: [cx + val, cy + h, depth], tex_coords: [ val, 0.0 ] },
         };
         let top = VertexBuffer::new(disp, &shp_top.get_vec()).unwrap();
 
@@ -156,42 +650,368 @@ impl<'a> Grid<'a>
         (main, top)
     }
 
-    //INPUTS
-    pub fn drop_pair(&mut self)
+    fn get_element_vertices(element : &Element, dataref : &ElementTypeList, s : f32) -> Vec<TextureVertex>
+    {
+        let depth = 1.0;
+        let dt = 0.25;
+
+        let (x, y) = element.get_pos();
+        let (tx, ty) = dataref.get_data(element.get_type()).get_atlas_offset();
+
+        let sq = Square {
+            top_left:     TextureVertex { position: [ x,  y+s, depth], tex_coords: [ tx, ty+dt ] },
+            top_right:    TextureVertex { position: [x+s, y+s, depth], tex_coords: [ tx+dt, ty+dt ] },
+            bottom_left:  TextureVertex { position: [ x,   y,  depth], tex_coords: [ tx, ty ] },
+            bottom_right: TextureVertex { position: [x+s,  y,  depth], tex_coords: [ tx+dt, ty ] },
+        };
+        sq.get_vec()
+    }
+    //Get the VertexBuffer for drawing a set of elements
+    fn get_element_buffer(&self, elements : Vec<&Element>) -> VertexBuffer<TextureVertex>
+    {
+        self.get_scaled_element_buffer(elements, 2.0)
+    }
+    //Same, with elements of another size than a tile
+    fn get_scaled_element_buffer(&self, elements : Vec<&Element>, size : f32) -> VertexBuffer<TextureVertex>
+    {
+        let data = &self.board.get_elements().element_data;
+        let mut texvec = vec![];
+        for element in elements
+        {
+            texvec.extend(Grid::get_element_vertices(element, data, size));
+        }
+        VertexBuffer::new(self.disp_ref, &texvec).unwrap()
+    }
+
+    //Get the VertexBuffer for plain squares over tiles, to tint them
+    fn get_highlight_buffer(&self, tiles : &Vec<(f32, f32)>) -> VertexBuffer<TextureVertex>
+    {
+        let s = 2.0;
+        let depth = 1.5; //Over the elements
+        let mut texvec = vec![];
+        for &(x, y) in tiles
+        {
+            let sq = Square {
+                top_left:     TextureVertex { position: [ x,  y+s, depth], tex_coords: [ 0.0, 1.0 ] },
+                top_right:    TextureVertex { position: [x+s, y+s, depth], tex_coords: [ 1.0, 1.0 ] },
+                bottom_left:  TextureVertex { position: [ x,   y,  depth], tex_coords: [ 0.0, 0.0 ] },
+                bottom_right: TextureVertex { position: [x+s,  y,  depth], tex_coords: [ 1.0, 0.0 ] },
+            };
+            texvec.extend(sq.get_vec());
+        }
+        VertexBuffer::new(self.disp_ref, &texvec).unwrap()
+    }
+    //Preview of the pair in control, only while it can be dropped
+    //Played out on a copy of the board, so only when the pair moved since
+    fn update_preview(&mut self)
+    {
+        if !self.settings.borrow().show_preview || *self.board.get_state() != GameState::PLAY || self.board.is_replaying()
+        {
+            self.preview = None;
+            self.preview_stale = true;
+        }
+        else if self.preview_stale
+        {
+            self.preview = self.board.get_elements().preview_drop();
+            self.preview_stale = false;
+        }
+    }
+
+    //First line of the text on the side of the grid, below the next and hold windows
+    fn get_hud_position() -> (f32, f32)
+    {
+        let hold = ElementArray::get_side_windows()[1];
+        (hold.0 + 0.25, hold.1 - 1.25)
+    }
+    //Get the VertexBuffer for the text on the side of the grid
+    fn get_hud_buffer(&self) -> VertexBuffer<TextureVertex>
     {
-        match self.game_state
+        let (x, top) = Grid::get_hud_position();
+        let size = 0.15;
+        let mut lines = vec![];
+        if let Some(player) = self.player
+        {
+            lines.push(format!("PLAYER {}", player + 1));
+        }
+        if let Some(left) = self.board.get_time_left()
         {
-            GameState::PLAY =>
+            let seconds = left.ceil() as u32;
+            lines.push(format!("TIME {}:{:02}", seconds / 60, seconds % 60));
+        }
+        if let Some(puzzle) = self.board.get_puzzle()
+        {
+            lines.push(if self.editor.is_some() { "TRYING OUT".to_owned() } else { format!("PUZZLE {}/{}", self.puzzle_index + 1, self.puzzles.len()) });
+            lines.push(puzzle.name.to_uppercase());
+            let data = &self.board.get_elements().element_data;
+            lines.push(match puzzle.goal
             {
-                self.game_state = GameState::FALLING;
-                self.elements.drop_pair();
-                self.elements.make_fall();
-            },
-            _ => (),
+                PuzzleGoal::MAKE(t) => format!("MAKE {}", data.get_name(&t)),
+                PuzzleGoal::CLEAR(t) => format!("CLEAR {}", data.get_name(&t)),
+                PuzzleGoal::SCORE(points) => format!("SCORE {}", points),
+            }.to_uppercase());
+            lines.push(format!("DROPS {}/{}", self.board.get_pairs_dropped(), puzzle.drops));
+        }
+        lines.extend(vec![
+            "SCORE".to_owned(),
+            self.board.get_score().to_string(),
+            "CHAIN".to_owned(),
+            format!("{} MAX {}", self.board.get_chain(), self.board.get_longest_chain()),
+        ]);
+        //Puzzles always deal the same
+        if self.board.get_puzzle().is_none()
+        {
+            lines.push("SEED".to_owned());
+            lines.push(self.board.get_seed().to_string());
+        }
+        if let Some(countdown) = self.board.get_garbage_countdown()
+        {
+            lines.push(format!("ASH IN {}", countdown));
+        }
+        if self.player.is_some()
+        {
+            lines.push(format!("ASH SENT {}", self.board.get_garbage_sent()));
+        }
+        if self.board.get_rules().is_timed()
+        {
+            lines.push(format!("SPEED {}", self.board.get_speed_level() + 1));
+        }
+        if let Some(ref preview) = self.preview
+        {
+            if preview.chain > 0
+            {
+                lines.push(format!("DROP +{} X{}", preview.score, preview.chain));
+            }
+        }
+        let placement_name = |placement|
+        {
+            match placement
+            {
+                ProductPlacement::LAST_DROPPED => "LAST",
+                ProductPlacement::LOWEST => "LOW",
+                ProductPlacement::CENTROID => "MID",
+            }
+        };
+        let (rules, next_rules) = (self.board.get_rules(), self.board.get_next_rules());
+        lines.push(format!("MERGE {}", placement_name(rules.product_placement)));
+        //Settings changed for the next game, puzzles set their own
+        if self.board.get_puzzle().is_none() && (next_rules.mode != rules.mode || next_rules.product_placement != rules.product_placement || next_rules.is_timed() != rules.is_timed())
+        {
+            lines.push("NEXT GAME".to_owned());
+            if next_rules.mode != rules.mode
+            {
+                lines.push(match next_rules.mode
+                {
+                    GameMode::CLASSIC => " CLASSIC",
+                    GameMode::TIME_ATTACK => " ATTACK",
+                    GameMode::PUZZLE => " PUZZLE",
+                }.to_owned());
+            }
+            if next_rules.product_placement != rules.product_placement
+            {
+                lines.push(format!(" MERGE {}", placement_name(next_rules.product_placement)));
+            }
+            if next_rules.is_timed() != rules.is_timed()
+            {
+                lines.push(if next_rules.is_timed() { " TIMED" } else { " UNTIMED" }.to_owned());
+            }
+        }
+        if self.board.is_replaying()
+        {
+            lines.push("REPLAY".to_owned());
+        }
+        if self.board.is_game_over() && self.board.get_puzzle().is_none() && self.player.is_none()
+        {
+            //Best scores for this size, the last game marked
+            lines.push("".to_owned());
+            lines.push("BEST".to_owned());
+            let high_scores = self.high_scores.borrow();
+            let table = high_scores.get_table(self.board.get_rules().mode, self.board.get_grid_size());
+            for (i, entry) in table.iter().take(5).enumerate()
+            {
+                let mark = if self.last_rank == Some(i) { ">" } else { " " };
+                lines.push(format!("{}{} {}", mark, i + 1, entry.score));
+            }
+        }
+
+        //The editor panel takes the whole side
+        if self.is_editing()
+        {
+            lines = self.get_editor_lines().into_iter().map(|(line, _)| line).collect();
+        }
+
+        //Over the hold window, until the next drop frees it again
+        let hold_label = if self.board.get_elements().can_hold() { "HOLD" } else { "HELD" };
+        let hold = ElementArray::get_side_windows()[1];
+        let mut texvec = text::get_text_vertices(hold_label, x, hold.1 + hold.3 - 0.75, size);
+        let mut y = top;
+        for line in &lines
+        {
+            texvec.extend(text::get_text_vertices(line, x, y, size));
+            y -= 0.95;
         }
+        VertexBuffer::new(self.disp_ref, &texvec).unwrap()
     }
-    pub fn rotate_pair(&mut self)
+    //Full high score table for the current size and mode
+    fn get_scores_buffer(&self) -> VertexBuffer<TextureVertex>
+    {
+        let x = -11.0;
+        let size = 0.13;
+        let mode = self.board.get_rules().mode;
+        let grid_size = self.board.get_grid_size() as i32;
+        let mut lines = vec![
+            format!("HIGH SCORES {}X{} {}", grid_size, grid_size, mode.get_name()),
+            "".to_owned(),
+            "   SCORE    HIGHEST  DATE       SEED".to_owned(),
+        ];
+        let high_scores = self.high_scores.borrow();
+        let table = high_scores.get_table(mode, self.board.get_grid_size());
+        for (i, entry) in table.iter().enumerate()
+        {
+            lines.push(format!("{:>2} {:<8} {:<8} {} {}", i + 1, entry.score, entry.highest, storage::format_date(entry.date), entry.seed));
+        }
+        if table.is_empty()
+        {
+            lines.push("NO SCORES YET".to_owned());
+        }
+        lines.push("".to_owned());
+        lines.push("H: BACK".to_owned());
+
+        let mut texvec = vec![];
+        let mut y = 9.0;
+        for line in &lines
+        {
+            texvec.extend(text::get_text_vertices(line, x, y, size));
+            y -= 1.2;
+        }
+        VertexBuffer::new(self.disp_ref, &texvec).unwrap()
+    }
+    //Results shown over the grid after a game over
+    fn get_results_buffer(&self) -> VertexBuffer<TextureVertex>
     {
-        match self.game_state
+        let x = ElementArray::ORIGIN.0 - (self.board.get_grid_size() as i32 as f32) + 0.5;
+        let middle = ElementArray::ORIGIN.1 + 3.0; //Of the grid and its top segment
+        let size = 0.15;
+        let highest = self.get_highest_name();
+        let rank = match self.last_rank
+        {
+            Some(r) => format!("NEW BEST #{}", r + 1),
+            None => "".to_owned(),
+        };
+        let puzzle = self.board.get_puzzle().is_some();
+        let solved = self.board.is_puzzle_solved();
+        let last_puzzle = self.puzzle_index + 1 >= self.puzzles.len();
+        let lines = vec![
+            "SCORE".to_owned(),
+            self.board.get_score().to_string(),
+            "HIGHEST".to_owned(),
+            highest,
+            "PAIRS".to_owned(),
+            self.board.get_pairs_dropped().to_string(),
+            "ASH CLEARED".to_owned(),
+            format!("{} OF {}", self.board.get_garbage_cleared(), self.board.get_garbage_received()),
+            rank,
+            match (puzzle, solved, last_puzzle)
+            {
+                _ if self.editor.is_some() => "ENTER: EDIT",
+                (false, _, _) => "ENTER: AGAIN",
+                (true, true, false) => "ENTER: NEXT",
+                (true, true, true) => "ENTER: DONE",
+                (true, false, _) => "ENTER: RETRY",
+            }.to_owned(),
+        ];
+
+        //Centered on the grid and its top segment
+        let title = match (puzzle, solved)
         {
-            GameState::PLAY =>
+            _ if self.versus_result.is_some() => match self.versus_result.unwrap()
             {
-                self.elements.rotate_pair();
+                VersusResult::WON => "WINNER",
+                VersusResult::LOST => "TOPPED OUT",
+                VersusResult::DRAW => "DRAW",
             },
-            _ => (),
+            (true, true) => "SOLVED",
+            (true, false) => "FAILED",
+            _ if self.board.is_out_of_time() => "TIME UP",
+            _ => "GAME OVER",
+        };
+        let mut texvec = text::get_text_vertices(title, x, middle + 4.0, 0.2);
+        let mut y = middle + 2.2;
+        for line in &lines
+        {
+            texvec.extend(text::get_text_vertices(line, x, y, size));
+            y -= 1.2;
         }
+        VertexBuffer::new(self.disp_ref, &texvec).unwrap()
+    }
+
+    //INPUTS
+    //Nothing moves until the saved game question is answered
+    pub fn drop_pair(&mut self)
+    {
+        if self.pending_resume.is_none() { self.board.drop_pair(); }
+    }
+    pub fn rotate_pair(&mut self)
+    {
+        if self.pending_resume.is_none() { self.board.rotate_pair(); }
+        self.preview_stale = true;
     }
     pub fn move_pair(&mut self, dx : i32)
     {
-        self.elements.move_pair(dx);
+        if self.pending_resume.is_none() { self.board.move_pair(dx); }
+        self.preview_stale = true;
+    }
+    pub fn hold_pair(&mut self)
+    {
+        if self.pending_resume.is_none() { self.board.hold_pair(); }
+        self.preview_stale = true;
+    }
+    //Darkens an area (x, y, width, height) by some amount, then writes text over it
+    fn draw_overlay(&self, frame: &mut glium::Frame, cam: &Camera, area: (f32, f32, f32, f32), darkness: f32, text_buffer: &VertexBuffer<TextureVertex>)
+    {
+        use glium::Surface;
+        use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter};
+        let indices = glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList);
+        let overlay_params = glium::DrawParameters 
+        {
+            blend : glium::Blend::alpha_blending(),
+            .. Default::default() //For all other parameters, set default
+        };
+        let (x, y, w, h) = area;
+        let depth = 1.5;
+        let dim = Square
+        {
+            top_left:     TextureVertex { position: [ x,   y+h, depth], tex_coords: [ 0.0, 1.0 ] },
+            top_right:    TextureVertex { position: [x+w,  y+h, depth], tex_coords: [ 1.0, 1.0 ] },
+            bottom_left:  TextureVertex { position: [ x,    y,  depth], tex_coords: [ 0.0, 0.0 ] },
+            bottom_right: TextureVertex { position: [x+w,   y,  depth], tex_coords: [ 1.0, 0.0 ] },
+        };
+        let dim_buffer = VertexBuffer::new(self.disp_ref, &dim.get_vec()).unwrap();
+        let dim_uniforms = uniform!
+        {
+            camera: cam.view_matrix,
+            tex: &self.tex_blank,
+            tint: [0.0, 0.0, 0.0, darkness],
+        };
+        frame.draw(&dim_buffer, &indices, self.tint_shader, &dim_uniforms, &overlay_params).unwrap();
+
+        let uniforms = uniform!
+        {
+            camera: cam.view_matrix,
+            tex: self.tex_font.sampled()
+                .magnify_filter(MagnifySamplerFilter::Nearest)
+                .minify_filter(MinifySamplerFilter::Nearest),
+        };
+        frame.draw(text_buffer, &indices, self.shader, &uniforms, &overlay_params).unwrap();
     }
 }
 
-impl<'a> traits::Drawable for Grid<'a>
+impl<'a> drawable::Drawable for Grid<'a>
 {
     fn draw(&self, frame: &mut glium::Frame, cam: &Camera)
     {
         use glium::Surface;
+        let placed = cam.get_placed(self.offset.0, self.offset.1, self.scale);
+        let cam = &placed;
         let indices = glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList);
 
         //Main Grid graphics
@@ -199,7 +1019,7 @@ impl<'a> traits::Drawable for Grid<'a>
             let uniforms = uniform!
             {
                 camera: cam.view_matrix,
-                tex: match self.grid_size
+                tex: match self.board.get_grid_size()
                 {
                     GridSize::FOUR => &self.tex_four,
                     GridSize::FIVE => &self.tex_five,
@@ -222,9 +1042,12 @@ impl<'a> traits::Drawable for Grid<'a>
         }
 
         let (dim_x, dim_y) = self.disp_ref.get_framebuffer_dimensions();
-        let x = - 3.0 - (self.grid_size as i32 as f32);
-        let y = x;
-        let w = 2.0 * (self.grid_size as i32 as f32);
+        let grid_size = self.board.get_grid_size();
+        let (ox, oy) = ElementArray::ORIGIN;
+        let x = ox - (grid_size as i32 as f32);
+        let y = oy - (grid_size as i32 as f32);
+        let [next, hold, queue] = ElementArray::get_side_windows();
+        let w = 2.0 * (grid_size as i32 as f32);
         let h = w + 6.0; //include top segment
 
         //Maingrid region
@@ -238,7 +1061,21 @@ impl<'a> traits::Drawable for Grid<'a>
         let next_params = glium::DrawParameters 
         {
             blend : glium::Blend::alpha_blending(),
-            scissor : Some(cam.get_pixel_coord(6.0 + 0.25, 8.0 + 0.25, 6.0 - 0.5, 4.0 - 0.5, dim_x, dim_y)),
+            scissor : Some(cam.get_pixel_coord(next.0, next.1, next.2, next.3, dim_x, dim_y)),
+            .. Default::default() //For all other parameters, set default
+        };
+        //Held-Pair region, right below
+        let hold_params = glium::DrawParameters 
+        {
+            blend : glium::Blend::alpha_blending(),
+            scissor : Some(cam.get_pixel_coord(hold.0, hold.1, hold.2, hold.3, dim_x, dim_y)),
+            .. Default::default() //For all other parameters, set default
+        };
+        //Queued-Pairs region, beside the next pair
+        let queue_params = glium::DrawParameters 
+        {
+            blend : glium::Blend::alpha_blending(),
+            scissor : Some(cam.get_pixel_coord(queue.0, queue.1, queue.2, queue.3, dim_x, dim_y)),
             .. Default::default() //For all other parameters, set default
         };
         //element graphics
@@ -246,12 +1083,146 @@ impl<'a> traits::Drawable for Grid<'a>
             let uniforms = uniform!
             {
                 camera: cam.view_matrix,
-                tex: &self.elements.texture,
+                tex: &self.tex_elements,
             };
-            let element_buffer = self.elements.get_buffer(self.disp_ref);
+            let elements = self.board.get_elements();
+            let element_buffer = self.get_element_buffer(elements.get_elements());
             frame.draw(&element_buffer, &indices, self.shader, &uniforms, &elem_params).unwrap();
-            let next_buffer = self.elements.get_next_buffer(self.disp_ref);
+            let next_buffer = self.get_element_buffer(elements.get_next_elements());
             frame.draw(&next_buffer, &indices, self.shader, &uniforms, &next_params).unwrap();
+            let queue_buffer = self.get_scaled_element_buffer(elements.get_queue_elements(), 1.0);
+            frame.draw(&queue_buffer, &indices, self.shader, &uniforms, &queue_params).unwrap();
+            let hold_buffer = self.get_element_buffer(elements.get_hold_elements());
+            frame.draw(&hold_buffer, &indices, self.shader, &uniforms, &hold_params).unwrap();
+        }
+
+        //Landing spot of the pair in control, faded
+        if self.settings.borrow().show_ghost && *self.board.get_state() == GameState::PLAY && !self.board.is_replaying()
+        {
+            let uniforms = uniform!
+            {
+                camera: cam.view_matrix,
+                tex: &self.tex_elements,
+                tint: [1.0, 1.0, 1.0, 0.35f32],
+            };
+            let ghosts = self.board.get_elements().get_ghost_elements();
+            let ghost_buffer = self.get_element_buffer(ghosts.iter().collect());
+            frame.draw(&ghost_buffer, &indices, self.tint_shader, &uniforms, &elem_params).unwrap();
+        }
+
+        //Groups the drop would make react, link after link of the chain, and what the first ones would become
+        if let Some(ref preview) = self.preview
+        {
+            for (link, tiles) in preview.links.iter().enumerate()
+            {
+                //Warmer for each link further down the chain
+                let warmth = 0.2 * (link.min(3) as f32);
+                let highlight_uniforms = uniform!
+                {
+                    camera: cam.view_matrix,
+                    tex: &self.tex_blank,
+                    tint: [1.0, 1.0 - warmth, 0.6 - warmth, 0.3f32],
+                };
+                let highlight_buffer = self.get_highlight_buffer(tiles);
+                frame.draw(&highlight_buffer, &indices, self.tint_shader, &highlight_uniforms, &elem_params).unwrap();
+            }
+
+            let product_uniforms = uniform!
+            {
+                camera: cam.view_matrix,
+                tex: &self.tex_elements,
+                tint: [1.0, 1.0, 1.0, 0.6f32],
+            };
+            let product_buffer = self.get_element_buffer(preview.products.iter().collect());
+            frame.draw(&product_buffer, &indices, self.tint_shader, &product_uniforms, &elem_params).unwrap();
+        }
+
+        //Time left before the pair drops by itself, as a bar shrinking above the grid
+        if let Some((left, length)) = self.board.get_drop_countdown()
+        {
+            let fraction = if length > 0.0 { left / length } else { 0.0 };
+            let depth = 1.5;
+            let bar_y = y + h + 0.25;
+            let bar_w = w * fraction;
+            let bar = Square
+            {
+                top_left:     TextureVertex { position: [   x,    bar_y + 0.5, depth], tex_coords: [ 0.0, 1.0 ] },
+                top_right:    TextureVertex { position: [x + bar_w, bar_y + 0.5, depth], tex_coords: [ 1.0, 1.0 ] },
+                bottom_left:  TextureVertex { position: [   x,       bar_y,    depth], tex_coords: [ 0.0, 0.0 ] },
+                bottom_right: TextureVertex { position: [x + bar_w,  bar_y,    depth], tex_coords: [ 1.0, 0.0 ] },
+            };
+            let bar_buffer = VertexBuffer::new(self.disp_ref, &bar.get_vec()).unwrap();
+            let bar_params = glium::DrawParameters 
+            {
+                blend : glium::Blend::alpha_blending(),
+                .. Default::default() //For all other parameters, set default
+            };
+            //Turns red as it runs out
+            let uniforms = uniform!
+            {
+                camera: cam.view_matrix,
+                tex: &self.tex_blank,
+                tint: [1.0, 0.3 + 0.7 * fraction, 0.2 + 0.3 * fraction, 0.9f32],
+            };
+            frame.draw(&bar_buffer, &indices, self.tint_shader, &uniforms, &bar_params).unwrap();
+        }
+
+        //Brightened copies fading over the products of catalyzed reactions
+        for element in self.board.get_elements().get_elements().into_iter().filter(|e| e.get_glow() > 0.0)
+        {
+            let glow = element.get_glow();
+            let uniforms = uniform!
+            {
+                camera: cam.view_matrix,
+                tex: &self.tex_elements,
+                tint: [1.0 + glow, 1.0 + glow, 1.0 + 0.5 * glow, glow],
+            };
+            let glow_buffer = self.get_element_buffer(vec![element]);
+            frame.draw(&glow_buffer, &indices, self.tint_shader, &uniforms, &elem_params).unwrap();
+        }
+
+        //HUD graphics
+        {
+            use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter};
+            let uniforms = uniform!
+            {
+                camera: cam.view_matrix,
+                //Keep the font pixels sharp
+                tex: self.tex_font.sampled()
+                    .magnify_filter(MagnifySamplerFilter::Nearest)
+                    .minify_filter(MinifySamplerFilter::Nearest),
+            };
+            let hud_params = glium::DrawParameters 
+            {
+                blend : glium::Blend::alpha_blending(),
+                .. Default::default() //For all other parameters, set default
+            };
+            let hud_buffer = self.get_hud_buffer();
+            frame.draw(&hud_buffer, &indices, self.shader, &uniforms, &hud_params).unwrap();
+        }
+
+        //Results graphics, over the frozen board
+        if self.board.is_game_over()
+        {
+            self.draw_overlay(frame, cam, (x, y, w, h), 0.7, &self.get_results_buffer());
+        }
+
+        //Resume question graphics
+        if self.pending_resume.is_some()
+        {
+            let middle = oy + 3.0; //Of the grid and its top segment
+            let mut texvec = text::get_text_vertices("RESUME", x + 0.5, middle + 4.0, 0.2);
+            texvec.extend(text::get_text_vertices("LAST GAME?", x + 0.5, middle + 2.2, 0.15));
+            texvec.extend(text::get_text_vertices("Y: YES", x + 0.5, middle + 0.2, 0.15));
+            texvec.extend(text::get_text_vertices("N: NO", x + 0.5, middle - 1.0, 0.15));
+            let question_buffer = VertexBuffer::new(self.disp_ref, &texvec).unwrap();
+            self.draw_overlay(frame, cam, (x, y, w, h), 0.7, &question_buffer);
+        }
+
+        //High score graphics, over everything
+        if self.show_scores
+        {
+            self.draw_overlay(frame, cam, (-12.0, -12.0, 24.0, 24.0), 0.9, &self.get_scores_buffer());
         }
     }
 }
@@ -260,52 +1231,21 @@ impl<'a> traits::Updatable for Grid<'a>
 {
     fn update(&mut self, delta_t : f32)
     {
-        match self.game_state
+        //Nothing runs until the saved game question is answered, time attacks included
+        if self.pending_resume.is_some()
         {
-            GameState::PLAY => //WAITING FOR FALL INPUT
-            {
-                //Deal with inputs directly
-                //We only need to update positions here
-                self.elements.move_elements(delta_t);
-            },
-            GameState::FALLING => //WAITING FOR ELEMENTS TO SETTLE
-            {
-                if self.elements.move_elements(delta_t)
-                {
-                    if self.elements.test_reactions()
-                    {
-                        self.game_state = GameState::REACTING;
-                    }
-                    else if self.elements.test_above()
-                    {
-                        //Continue playing
-                        self.elements.set_next_position(true); //Move next pair away
-                        self.game_state = GameState::READYING;
-                    }
-                    else
-                    {
-                        //game over
-                        let size = self.grid_size;
-                        self.reset_grid(size);
-                    }
-                }
-            },
-            GameState::REACTING => //WAITING FOR ELEMENTS TO STOP REACTING
-            {
-                self.elements.make_fall();
-                self.game_state = GameState::FALLING;
-            },
-            GameState::READYING => //WAITING FOR NEXT PAIR ANIMATION
-            {
-                if self.elements.move_elements(delta_t)
-                {
-                    self.elements.set_next_position(false);
-                    self.elements.get_next_pair(true);
-                    self.game_state = GameState::PLAY;
-                }
-            },
-            _ => (),
+            return
+        }
+        self.board.update(delta_t);
+        self.update_preview();
+        if !self.board.is_game_over()
+        {
+            self.result_recorded = false;
+            self.last_rank = None;
+        }
+        else if !self.result_recorded
+        {
+            self.record_result();
         }
-        self.time += delta_t;
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,229 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use board::GridSize;
+use rules::GameMode;
+use storage;
+
+const HEADER : &str = "metalchemist-highscores 1";
+//Entries kept for each grid size and game mode
+const TABLE_LENGTH : usize = 10;
+
+#[derive(Clone, Debug)]
+pub struct HighScore
+{
+    pub mode : GameMode,
+    pub grid_size : GridSize,
+    pub score : u64,
+    pub highest : String, //Name of the highest element reached
+    pub date : u64, //Timestamp, see storage::format_date
+    pub seed : u64,
+}
+impl HighScore
+{
+    //One line per entry, tab separated
+    fn to_line(&self) -> String
+    {
+        format!("{}\t{}\t{}\t{}\t{}\t{}",
+            self.mode.get_name(), self.grid_size as i32, self.score, self.highest, self.date, self.seed)
+    }
+    fn from_line(line : &str) -> Option<HighScore>
+    {
+        let fields : Vec<&str> = line.trim().split('\t').collect();
+        if fields.len() != 6
+        {
+            return None
+        }
+        Some(HighScore
+        {
+            mode : GameMode::from_name(fields[0])?,
+            grid_size : GridSize::from_value(fields[1].parse().ok()?)?,
+            score : fields[2].parse().ok()?,
+            highest : fields[3].to_owned(),
+            date : fields[4].parse().ok()?,
+            seed : fields[5].parse().ok()?,
+        })
+    }
+    fn same_table(&self, mode : GameMode, size : GridSize) -> bool
+    {
+        self.mode == mode && self.grid_size == size
+    }
+}
+
+pub struct HighScores
+{
+    path : PathBuf,
+    entries : Vec<HighScore>, //Best first
+}
+
+impl HighScores
+{
+    pub fn get_default_path() -> PathBuf
+    {
+        storage::get_data_dir().join("highscores.txt")
+    }
+
+    //A missing or unreadable file gives empty tables, malformed lines are skipped
+    pub fn load(path : PathBuf) -> HighScores
+    {
+        let mut scores = HighScores { path : path, entries : vec![] };
+        match fs::read_to_string(&scores.path)
+        {
+            Ok(contents) =>
+            {
+                let mut lines = contents.lines();
+                if lines.next().map(|l| l.trim()) != Some(HEADER)
+                {
+                    storage::report(&format!("Ignoring unrecognized high score file {}", scores.path.display()));
+                    return scores
+                }
+                for line in lines
+                {
+                    match HighScore::from_line(line)
+                    {
+                        Some(entry) => { scores.insert(entry); },
+                        None => if !line.trim().is_empty() { storage::report(&format!("Skipped high score line: {}", line)) },
+                    }
+                }
+            },
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => (),
+            Err(e) => storage::report(&format!("Could not read high scores {}: {}", scores.path.display(), e)),
+        }
+        scores
+    }
+
+    pub fn save(&self) -> io::Result<()>
+    {
+        let mut contents = HEADER.to_owned();
+        contents.push('\n');
+        for entry in &self.entries
+        {
+            contents.push_str(&entry.to_line());
+            contents.push('\n');
+        }
+        storage::write_atomically(&self.path, &contents)
+    }
+
+    //Rank of the new entry in its table (0 is best), None if it did not make it
+    pub fn insert(&mut self, entry : HighScore) -> Option<usize>
+    {
+        let rank = self.get_table(entry.mode, entry.grid_size).iter().filter(|e| e.score >= entry.score).count();
+        if rank >= TABLE_LENGTH
+        {
+            return None
+        }
+        let index = self.entries.iter().position(|e| e.score < entry.score).unwrap_or(self.entries.len());
+        self.entries.insert(index, entry);
+
+        //Drop whatever fell off the bottom of each table
+        let mut kept : Vec<HighScore> = Vec::with_capacity(self.entries.len());
+        for entry in self.entries.drain(..)
+        {
+            if kept.iter().filter(|e| e.same_table(entry.mode, entry.grid_size)).count() < TABLE_LENGTH
+            {
+                kept.push(entry);
+            }
+        }
+        self.entries = kept;
+        Some(rank)
+    }
+
+    //Best first
+    pub fn get_table(&self, mode : GameMode, size : GridSize) -> Vec<&HighScore>
+    {
+        self.entries.iter().filter(|e| e.same_table(mode, size)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use std::env;
+
+    fn entry(score : u64) -> HighScore
+    {
+        HighScore { mode : GameMode::CLASSIC, grid_size : GridSize::SIX, score : score, highest : "salt".to_owned(), date : 0, seed : score }
+    }
+    fn scores(table : Vec<&HighScore>) -> Vec<u64>
+    {
+        table.iter().map(|e| e.score).collect()
+    }
+
+    #[test]
+    fn entries_go_in_best_first()
+    {
+        let mut table = HighScores { path : PathBuf::new(), entries : vec![] };
+        assert_eq!(table.insert(entry(50)), Some(0));
+        assert_eq!(table.insert(entry(80)), Some(0));
+        assert_eq!(table.insert(entry(60)), Some(1));
+        //A tie goes below the score it ties with
+        let mut tie = entry(60);
+        tie.seed = 1;
+        assert_eq!(table.insert(tie), Some(2));
+        let table_now = table.get_table(GameMode::CLASSIC, GridSize::SIX);
+        assert_eq!(scores(table_now.clone()), vec![80, 60, 60, 50]);
+        assert_eq!(table_now[1].seed, 60);
+        assert_eq!(table_now[2].seed, 1);
+    }
+
+    #[test]
+    fn tables_keep_their_best_ones_apart()
+    {
+        let mut table = HighScores { path : PathBuf::new(), entries : vec![] };
+        for score in 1..(TABLE_LENGTH as u64 + 1)
+        {
+            table.insert(entry(score * 10));
+        }
+        //Too low, or only tying with the last one
+        assert_eq!(table.insert(entry(5)), None);
+        assert_eq!(table.insert(entry(10)), None);
+        //In, pushing the last one out
+        assert_eq!(table.insert(entry(15)), Some(TABLE_LENGTH - 1));
+        let classic = table.get_table(GameMode::CLASSIC, GridSize::SIX);
+        assert_eq!(classic.len(), TABLE_LENGTH);
+        assert_eq!(classic.last().unwrap().score, 15);
+        //Other sizes and modes have tables of their own
+        let mut small = entry(1);
+        small.grid_size = GridSize::FOUR;
+        assert_eq!(table.insert(small), Some(0));
+        let mut timed = entry(1);
+        timed.mode = GameMode::TIME_ATTACK;
+        assert_eq!(table.insert(timed), Some(0));
+        assert_eq!(table.get_table(GameMode::CLASSIC, GridSize::SIX).len(), TABLE_LENGTH);
+        assert_eq!(scores(table.get_table(GameMode::CLASSIC, GridSize::FOUR)), vec![1]);
+        assert_eq!(scores(table.get_table(GameMode::TIME_ATTACK, GridSize::SIX)), vec![1]);
+    }
+
+    #[test]
+    fn tables_round_trip()
+    {
+        let path = env::temp_dir().join("metalchemist-test-highscores.txt");
+        let mut table = HighScores { path : path.clone(), entries : vec![] };
+        table.insert(entry(30));
+        table.insert(entry(70));
+        table.save().unwrap();
+        let loaded = HighScores::load(path.clone());
+        let _ = fs::remove_file(&path);
+        let best = loaded.get_table(GameMode::CLASSIC, GridSize::SIX);
+        assert_eq!(scores(best.clone()), vec![70, 30]);
+        assert_eq!(best[0].to_line(), entry(70).to_line());
+    }
+
+    #[test]
+    fn missing_or_unrecognized_files_give_empty_tables()
+    {
+        let path = env::temp_dir().join("metalchemist-test-no-highscores.txt");
+        let _ = fs::remove_file(&path);
+        assert!(HighScores::load(path.clone()).entries.is_empty());
+
+        fs::write(&path, "something else\nclassic\t6\t100\tsalt\t0\t1\n").unwrap();
+        let unrecognized = HighScores::load(path.clone());
+        //Lines that do not read are skipped, the others kept
+        fs::write(&path, format!("{}\nclassic\t6\tlots\tsalt\t0\t1\nclassic\t6\t100\tsalt\t0\t1\nclassic\t6\n", HEADER)).unwrap();
+        let damaged = HighScores::load(path.clone());
+        let _ = fs::remove_file(&path);
+        assert!(unrecognized.entries.is_empty());
+        assert_eq!(scores(damaged.get_table(GameMode::CLASSIC, GridSize::SIX)), vec![100]);
+    }
+}
@@ -0,0 +1,19 @@
+//Metalchemist core: board and rules, without any renderer.
+//The glium frontend (main.rs) is a thin layer drawing and driving a Board.
+extern crate rand;
+
+pub mod traits;
+pub mod board;
+pub mod rules;
+pub mod element_array;
+pub mod element;
+pub mod rng;
+pub mod sections;
+pub mod spawn;
+pub mod storage;
+pub mod highscores;
+pub mod replay;
+pub mod save;
+pub mod puzzle;
+pub mod editor;
+pub mod settings;
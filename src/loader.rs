@@ -15,4 +15,11 @@ pub fn get_sprite(display: &glium::Display, filepath: &str) -> Texture2d
     //Debug info
     println!("loaded {}, {:?}", filepath, dimensions);
     Texture2d::new(display, image).unwrap()
-}
\ No newline at end of file
+}
+
+//Plain white texture, to draw solid shapes with the tinted shader
+pub fn get_blank(display: &glium::Display) -> Texture2d
+{
+    let image = glium::texture::RawImage2d::from_raw_rgba(vec![255u8, 255, 255, 255], (1, 1));
+    Texture2d::new(display, image).unwrap()
+}
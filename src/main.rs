@@ -1,24 +1,90 @@
 #[macro_use]
 extern crate glium;
 extern crate image;
-extern crate rand;
+extern crate metalchemist;
 use glium::glutin;
 use std::time::Instant;
+use std::rc::Rc;
+use std::cell::RefCell;
+use metalchemist::board::GridSize;
+use metalchemist::element::ElementTypeList;
+use metalchemist::puzzle::Puzzle;
+use metalchemist::replay::Replay;
+use metalchemist::save::SaveGame;
+use metalchemist::storage;
+use metalchemist::highscores::HighScores;
+use metalchemist::settings::Settings;
 
 mod camera;
 mod vertex;
 mod shaders;
 mod loader;
+mod text;
 
 //GameObjects
-mod traits;
+mod drawable;
 mod grid;
-mod element_array;
-mod element;
 mod inputs;
+mod versus;
 
 fn main() 
 {
+    //Command line: --seed N to replay the elements of a previous game
+    //--replay FILE to watch a recorded game
+    let args : Vec<String> = std::env::args().collect();
+    let mut start_seed = None;
+    let mut start_replay = None;
+    for i in 1..args.len()
+    {
+        if args[i] == "--seed" && i + 1 < args.len()
+        {
+            start_seed = args[i + 1].parse::<u64>().ok();
+            if start_seed.is_none() { storage::report(&format!("Invalid seed: {}", args[i + 1])); }
+        }
+        if args[i] == "--replay" && i + 1 < args.len()
+        {
+            match Replay::load(std::path::Path::new(&args[i + 1]))
+            {
+                Ok(replay) => start_replay = Some(replay),
+                Err(e) => storage::report(&format!("Could not load replay {}", e)),
+            }
+        }
+    }
+
+    //Element definitions can be tweaked without rebuilding, a broken file stops here
+    let element_path = ElementTypeList::get_default_path();
+    let element_data = if element_path.exists()
+    {
+        match ElementTypeList::load(&element_path)
+        {
+            Ok(data) => data,
+            Err(e) =>
+            {
+                storage::report(&format!("Could not load elements {}", e));
+                std::process::exit(1);
+            },
+        }
+    }
+    else
+    {
+        ElementTypeList::new()
+    };
+    //Puzzles only need the elements they use, the game goes on without them
+    let puzzle_path = Puzzle::get_default_path();
+    let puzzles = if puzzle_path.exists()
+    {
+        Puzzle::load_pack(&puzzle_path, &element_data)
+    }
+    else
+    {
+        Puzzle::get_built_in(&element_data)
+    };
+    let puzzles = puzzles.unwrap_or_else(|e|
+    {
+        storage::report(&format!("Could not load puzzles {}", e));
+        vec![]
+    });
+
     //Initialize graphics
     let mut events_loop = glutin::EventsLoop::new();
     let window = glutin::WindowBuilder::new()
@@ -38,8 +104,36 @@ fn main()
     let background = loader::get_sprite(&display, "Placeholder.png");
 
     //GameObjects
-    let mut grid_inst = grid::Grid::new(&display, &prog);
-    grid_inst.reset_grid(grid::GridSize::SIX);
+    //Loaded once, every grid on screen shares them
+    let high_scores = Rc::new(RefCell::new(HighScores::load(HighScores::get_default_path())));
+    let settings = Rc::new(RefCell::new(Settings::load(Settings::get_default_path())));
+    let mut grid_inst = grid::Grid::new(&display, &program_manager, high_scores.clone(), settings.clone());
+    grid_inst.set_element_data(element_data.clone());
+    grid_inst.set_puzzles(puzzles);
+    match start_seed
+    {
+        Some(seed) => grid_inst.reset_grid_with_seed(GridSize::SIX, seed),
+        None => grid_inst.reset_grid(GridSize::SIX),
+    }
+    let asked_for_game = start_seed.is_some() || start_replay.is_some();
+    if let Some(replay) = start_replay
+    {
+        grid_inst.play_replay(replay);
+    }
+    //Offer to pick up where the last session left off
+    let save_path = SaveGame::get_default_path();
+    if asked_for_game
+    {
+        grid_inst.keep_saved_game();
+    }
+    else if save_path.exists()
+    {
+        match SaveGame::load(&save_path)
+        {
+            Ok(save) => grid_inst.offer_resume(save),
+            Err(e) => storage::report(&format!("Could not load saved game {}", e)),
+        }
+    }
 
     use vertex::TextureVertex;
     vertex::macrocall();
@@ -65,6 +159,11 @@ fn main()
 
     //Remembers previous button states
     let mut input_memory = inputs::Inputs { rotate : false, left : false, right : false, drop : false };
+    //Mouse position in window pixels, for clicks
+    let mut cursor = (0.0f64, 0.0f64);
+    let mut window_size = (800u32, 800u32);
+    //Two player game, in place of the single grid while it lasts
+    let mut versus : Option<versus::Versus> = None;
 
     //MAIN LOOP
     let mut exit_condition = false;
@@ -87,9 +186,13 @@ fn main()
             tex: &background,
         };
         //CALL DRAWS HERE
-        frame.draw(&background_buffer, &indices, &prog, &uniforms, &Default::default()).unwrap();
-        use traits::Drawable;
-        grid_inst.draw(&mut frame, &main_camera);
+        frame.draw(&background_buffer, &indices, prog, &uniforms, &Default::default()).unwrap();
+        use drawable::Drawable;
+        match versus
+        {
+            Some(ref v) => v.draw(&mut frame, &main_camera),
+            None => grid_inst.draw(&mut frame, &main_camera),
+        }
 
         frame.finish().unwrap();
 
@@ -103,10 +206,43 @@ fn main()
                 glutin::Event::WindowEvent { event: w_event, .. } => match w_event
                 {
                     glutin::WindowEvent::Closed => exit_condition = true,
-                    glutin::WindowEvent::Resized {0: width, 1: height} => main_camera.adjust_width_height(width, height),
+                    glutin::WindowEvent::Resized {0: width, 1: height} =>
+                    {
+                        main_camera.adjust_width_height(width, height);
+                        window_size = (width, height);
+                    },
+                    glutin::WindowEvent::CursorMoved { position, .. } => cursor = position,
+                    glutin::WindowEvent::MouseInput { state: glutin::ElementState::Pressed, button, .. } if versus.is_none() =>
+                    {
+                        let (x, y) = main_camera.get_world_coord(cursor.0, cursor.1, window_size.0, window_size.1);
+                        match button
+                        {
+                            glutin::MouseButton::Left => grid_inst.click(x, y, true),
+                            glutin::MouseButton::Right => grid_inst.click(x, y, false),
+                            _ => (),
+                        }
+                    },
                     glutin::WindowEvent::KeyboardInput { input: k_input, .. } => 
                     match k_input.virtual_keycode.unwrap()
                     {
+                        VirtualKeyCode::F2 =>
+                        {
+                            let now = k_input.state == glutin::ElementState::Pressed;
+                            if now
+                            {
+                                versus = match versus
+                                {
+                                    Some(_) => None,
+                                    None => Some(versus::Versus::new(&display, &program_manager, &element_data, &high_scores, &settings)),
+                                };
+                            }
+                        },
+                        //Each player has their own keys
+                        key if versus.is_some() =>
+                        {
+                            let now = k_input.state == glutin::ElementState::Pressed;
+                            if let Some(ref mut v) = versus { v.key(key, now); }
+                        },
                         VirtualKeyCode::W =>
                         {
                             let now = k_input.state == glutin::ElementState::Pressed;
@@ -117,6 +253,91 @@ fn main()
                             let now = k_input.state == glutin::ElementState::Pressed;
                             if now { grid_inst.downscale(); }
                         },
+                        VirtualKeyCode::F5 =>
+                        {
+                            let now = k_input.state == glutin::ElementState::Pressed;
+                            if now
+                            {
+                                if let Some(path) = grid_inst.save_replay() { storage::report(&format!("Saved replay {}", path)); }
+                            }
+                        },
+                        VirtualKeyCode::F9 =>
+                        {
+                            let now = k_input.state == glutin::ElementState::Pressed;
+                            if now
+                            {
+                                match Replay::load(&grid::Grid::get_last_replay_path())
+                                {
+                                    Ok(replay) => grid_inst.play_replay(replay),
+                                    Err(e) => storage::report(&format!("Could not load replay {}", e)),
+                                }
+                            }
+                        },
+                        VirtualKeyCode::Y =>
+                        {
+                            let now = k_input.state == glutin::ElementState::Pressed;
+                            if now { grid_inst.answer_resume(true); }
+                        },
+                        VirtualKeyCode::N =>
+                        {
+                            let now = k_input.state == glutin::ElementState::Pressed;
+                            if now { grid_inst.answer_resume(false); }
+                        },
+                        VirtualKeyCode::P =>
+                        {
+                            let now = k_input.state == glutin::ElementState::Pressed;
+                            if now { grid_inst.cycle_placement(); }
+                        },
+                        VirtualKeyCode::C =>
+                        {
+                            let now = k_input.state == glutin::ElementState::Pressed;
+                            if now { grid_inst.hold_pair(); }
+                        },
+                        VirtualKeyCode::U =>
+                        {
+                            let now = k_input.state == glutin::ElementState::Pressed;
+                            if now { grid_inst.toggle_puzzles(); }
+                        },
+                        VirtualKeyCode::R =>
+                        {
+                            let now = k_input.state == glutin::ElementState::Pressed;
+                            if now { grid_inst.retry_puzzle(); }
+                        },
+                        VirtualKeyCode::E =>
+                        {
+                            let now = k_input.state == glutin::ElementState::Pressed;
+                            if now { grid_inst.toggle_editor(); }
+                        },
+                        VirtualKeyCode::M =>
+                        {
+                            let now = k_input.state == glutin::ElementState::Pressed;
+                            if now { grid_inst.cycle_mode(); }
+                        },
+                        VirtualKeyCode::T =>
+                        {
+                            let now = k_input.state == glutin::ElementState::Pressed;
+                            if now { grid_inst.toggle_timer(); }
+                        },
+                        VirtualKeyCode::G =>
+                        {
+                            let now = k_input.state == glutin::ElementState::Pressed;
+                            if now { grid_inst.toggle_ghost(); }
+                        },
+                        VirtualKeyCode::V =>
+                        {
+                            let now = k_input.state == glutin::ElementState::Pressed;
+                            if now { grid_inst.toggle_preview(); }
+                        },
+                        VirtualKeyCode::H =>
+                        {
+                            let now = k_input.state == glutin::ElementState::Pressed;
+                            if now { grid_inst.toggle_scores(); }
+                        },
+                        VirtualKeyCode::Return =>
+                        {
+                            let now = k_input.state == glutin::ElementState::Pressed;
+                            if now { grid_inst.restart(); }
+                        },
                         VirtualKeyCode::Down => 
                         {
                             let now = k_input.state == glutin::ElementState::Pressed;
@@ -150,7 +371,14 @@ fn main()
         });
 
         //CALL UPDATES HERE
-        use traits::Updatable;
-        grid_inst.update(dt);
+        use metalchemist::traits::Updatable;
+        match versus
+        {
+            Some(ref mut v) => v.update(dt),
+            None => grid_inst.update(dt),
+        }
     }
+
+    //Keep the game in progress for next launch
+    grid_inst.save_game();
 }
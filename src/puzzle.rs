@@ -0,0 +1,254 @@
+use board::GridSize;
+use element::{ElementType, ElementTypeList};
+use sections;
+use sections::{Entry, Section};
+use storage;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+//Kept at the top of saved packs, as in content/puzzles.txt
+const PACK_HEADER : &str = "\
+# Puzzle pack, played in order from the first one.
+#
+# [name]  one section per puzzle
+# size    grid size, from 4 to 8
+# board   starting board, as rows from the top separated by /, with - for an empty tile;
+#         the rows rest on the bottom of the grid (empty if left out)
+# pairs   pairs dealt in order, separated by commas, the left element first
+# goal    make <element>   some reaction makes it
+#         clear <element>  none of it is left on the board
+#         score <points>   at least that many points
+# drops   drops allowed to meet the goal (one per pair if left out)
+";
+
+//What a puzzle asks for before its drops run out
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PuzzleGoal
+{
+    MAKE(ElementType), //Some reaction makes it
+    CLEAR(ElementType), //None of it left on the board
+    SCORE(u64), //At least that many points
+}
+
+//Starting board, pairs dealt in order, and a goal
+#[derive(Clone)]
+pub struct Puzzle
+{
+    pub name : String,
+    pub grid_size : GridSize,
+    //Px * H + Py = Index, like in ElementArray
+    pub tiles : Vec<Option<ElementType>>,
+    pub pairs : Vec<(ElementType, ElementType)>,
+    pub goal : PuzzleGoal,
+    pub drops : u32, //Drops allowed, at most one per pair
+}
+
+impl Puzzle
+{
+    pub fn get_default_path() -> PathBuf
+    {
+        PathBuf::from("./content/puzzles.txt")
+    }
+    //Puzzles shipped with the game, copied from content/puzzles.txt at build time
+    pub fn get_built_in(data : &ElementTypeList) -> Result<Vec<Puzzle>, String>
+    {
+        Puzzle::parse_pack(include_str!("../content/puzzles.txt"), "built-in puzzles", data)
+    }
+    pub fn load_pack(path : &Path, data : &ElementTypeList) -> Result<Vec<Puzzle>, String>
+    {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Puzzle::parse_pack(&text, &path.display().to_string(), data)
+    }
+
+    //Writes the whole pack over, comments other than the header are lost
+    pub fn save_pack(path : &Path, puzzles : &[Puzzle], data : &ElementTypeList) -> io::Result<()>
+    {
+        let mut contents = PACK_HEADER.to_owned();
+        for puzzle in puzzles
+        {
+            contents.push('\n');
+            contents.push_str(&puzzle.to_section(data));
+        }
+        storage::write_atomically(path, &contents)
+    }
+    //Same format as read by parse_pack
+    pub fn to_section(&self, data : &ElementTypeList) -> String
+    {
+        let width = self.grid_size as i32;
+        let height = width + 2;
+        let name = |t : &Option<ElementType>| t.map(|t| data.get_name(&t).to_owned()).unwrap_or("-".to_owned());
+        let mut lines = vec![
+            format!("[{}]", self.name),
+            format!("size = {}", width),
+        ];
+        //Top row first, down from the highest one used
+        let used = (0..width).filter(|&py| (0..width).any(|px| self.tiles[(px * height + py) as usize].is_some())).max();
+        if let Some(top) = used
+        {
+            let rows : Vec<String> = (0..top + 1).rev()
+                .map(|py| (0..width).map(|px| name(&self.tiles[(px * height + py) as usize])).collect::<Vec<String>>().join(" "))
+                .collect();
+            lines.push(format!("board = {}", rows.join(" / ")));
+        }
+        let pairs : Vec<String> = self.pairs.iter().map(|&(a, b)| format!("{} {}", name(&Some(a)), name(&Some(b)))).collect();
+        lines.push(format!("pairs = {}", pairs.join(", ")));
+        lines.push(match self.goal
+        {
+            PuzzleGoal::MAKE(t) => format!("goal = make {}", name(&Some(t))),
+            PuzzleGoal::CLEAR(t) => format!("goal = clear {}", name(&Some(t))),
+            PuzzleGoal::SCORE(points) => format!("goal = score {}", points),
+        });
+        if self.drops as usize != self.pairs.len()
+        {
+            lines.push(format!("drops = {}", self.drops));
+        }
+        lines.join("\n") + "\n"
+    }
+
+    //One section per puzzle, in the order they are played, see content/puzzles.txt for the format
+    pub fn parse_pack(text : &str, source : &str, data : &ElementTypeList) -> Result<Vec<Puzzle>, String>
+    {
+        sections::parse(text, source)?.iter().map(|s| Puzzle::parse_section(s, source, data)).collect()
+    }
+    fn parse_section(section : &Section, source : &str, data : &ElementTypeList) -> Result<Puzzle, String>
+    {
+        section.check_keys(&["size", "board", "pairs", "goal", "drops"], source)?;
+        let find = |name : &str, entry : &Entry| data.from_name(name).ok_or_else(|| entry.error(source, &format!("unknown element \"{}\"", name)));
+
+        let size_entry = section.require("size", source)?;
+        let grid_size = GridSize::from_value(size_entry.parse::<i32>(source)?)
+            .ok_or_else(|| size_entry.error(source, "expected a size from 4 to 8"))?;
+        let width = grid_size as i32;
+        let height = width + 2;
+
+        //Rows from the top, resting on the bottom of the grid
+        let mut tiles = vec![None; (width * height) as usize];
+        if let Some(entry) = section.get("board")
+        {
+            let rows : Vec<Vec<&str>> = entry.value.split('/').map(|r| r.split_whitespace().collect()).collect();
+            if rows.len() > width as usize || rows.iter().any(|r| r.len() != width as usize)
+            {
+                return Err(entry.error(source, &format!("expected up to {} rows of {} tiles, separated by /", width, width)))
+            }
+            for (i, row) in rows.iter().enumerate()
+            {
+                let py = (rows.len() - 1 - i) as i32;
+                for (px, name) in row.iter().enumerate()
+                {
+                    if *name != "-"
+                    {
+                        tiles[(px as i32 * height + py) as usize] = Some(find(name, entry)?);
+                    }
+                }
+            }
+        }
+
+        let pairs_entry = section.require("pairs", source)?;
+        let mut pairs = vec![];
+        for pair in pairs_entry.value.split(',')
+        {
+            match pair.split_whitespace().collect::<Vec<&str>>().as_slice()
+            {
+                [a, b] => pairs.push((find(a, pairs_entry)?, find(b, pairs_entry)?)),
+                _ => return Err(pairs_entry.error(source, &format!("expected pairs of two elements separated by commas, found \"{}\"", pair.trim()))),
+            }
+        }
+
+        let goal_entry = section.require("goal", source)?;
+        let goal = match goal_entry.words().as_slice()
+        {
+            ["make", name] => PuzzleGoal::MAKE(find(name, goal_entry)?),
+            ["clear", name] => PuzzleGoal::CLEAR(find(name, goal_entry)?),
+            ["score", points] => PuzzleGoal::SCORE(points.parse::<u64>().map_err(|_| goal_entry.error(source, "expected a number of points"))?),
+            _ => return Err(goal_entry.error(source, "expected \"make <element>\", \"clear <element>\" or \"score <points>\"")),
+        };
+
+        let drops = match section.get("drops")
+        {
+            Some(entry) =>
+            {
+                let drops = entry.parse::<u32>(source)?;
+                if drops == 0 || drops as usize > pairs.len()
+                {
+                    return Err(entry.error(source, &format!("expected from 1 to {} drops, one per pair", pairs.len())))
+                }
+                drops
+            },
+            None => pairs.len() as u32,
+        };
+
+        Ok(Puzzle
+        {
+            name : section.name.clone(),
+            grid_size : grid_size,
+            tiles : tiles,
+            pairs : pairs,
+            goal : goal,
+            drops : drops,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn parse(text : &str) -> Result<Vec<Puzzle>, String>
+    {
+        Puzzle::parse_pack(text, "test", &ElementTypeList::new())
+    }
+
+    #[test]
+    fn pack_round_trip()
+    {
+        let data = ElementTypeList::new();
+        let puzzles = Puzzle::get_built_in(&data).unwrap();
+        assert!(!puzzles.is_empty());
+        let text : Vec<String> = puzzles.iter().map(|p| p.to_section(&data)).collect();
+        let again = parse(&text.join("\n")).unwrap();
+        assert_eq!(again.len(), puzzles.len());
+        for (puzzle, read) in puzzles.iter().zip(again.iter())
+        {
+            assert_eq!(read.name, puzzle.name);
+            assert_eq!(read.grid_size, puzzle.grid_size);
+            assert_eq!(read.tiles, puzzle.tiles);
+            assert_eq!(read.pairs, puzzle.pairs);
+            assert_eq!(read.goal, puzzle.goal);
+            assert_eq!(read.drops, puzzle.drops);
+        }
+    }
+
+    #[test]
+    fn board_rows_rest_on_the_bottom()
+    {
+        let data = ElementTypeList::new();
+        let puzzle = parse("[rows]\nsize = 4\nboard = salt - - - / air - - fire\npairs = air air, fire fire\ngoal = clear salt\ndrops = 1\n").unwrap().remove(0);
+        //Px * H + Py, with H = 6
+        assert_eq!(puzzle.tiles[0], data.from_name("air"));
+        assert_eq!(puzzle.tiles[1], data.from_name("salt"));
+        assert_eq!(puzzle.tiles[3 * 6], data.from_name("fire"));
+        assert_eq!(puzzle.tiles.iter().filter(|t| t.is_some()).count(), 3);
+        assert_eq!(puzzle.pairs.len(), 2);
+        assert_eq!(puzzle.goal, PuzzleGoal::CLEAR(data.from_name("salt").unwrap()));
+        assert_eq!(puzzle.drops, 1);
+    }
+
+    #[test]
+    fn bad_puzzles_are_refused()
+    {
+        let with = |board : &str, rest : &str| parse(&format!("[bad]\nsize = 4\nboard = {}\n{}", board, rest));
+        let rest = "pairs = air air\ngoal = score 10\n";
+        assert!(with("air air - -", rest).is_ok());
+        let unknown = with("air gravel - -", rest).err().unwrap();
+        assert!(unknown.contains("unknown element \"gravel\""), "{}", unknown);
+        //Rows of the wrong width, or more rows than the grid has
+        assert!(with("air air -", rest).is_err());
+        assert!(with("- - - - / - - - - / - - - - / - - - - / air - - -", rest).is_err());
+        assert!(with("- - - -", "pairs = air\ngoal = score 10\n").is_err());
+        assert!(with("- - - -", "pairs = air air\ngoal = make gravel\n").is_err());
+        assert!(with("- - - -", "pairs = air air\ngoal = score 10\ndrops = 2\n").is_err());
+        assert!(with("- - - -", "pairs = air air\n").is_err());
+    }
+}
@@ -0,0 +1,241 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use board::GridSize;
+use rules::{GameMode, GameRules, ProductPlacement};
+use spawn::SpawnKind;
+use storage;
+
+//Older versions left out some of the rules and the elements, and cannot be played back exactly
+const HEADER : &str = "metalchemist-replay 3";
+//Lines of the rules a replay needs, all of them
+const RULE_KEYS : &[&str] = &["mode", "spawn", "placement", "next", "combo", "penalty", "timer"];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ReplayAction
+{
+    MOVE(i32),
+    ROTATE,
+    DROP,
+    HOLD,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct ReplayStep
+{
+    pub time : f32, //Seconds since the start of the game
+    pub action : ReplayAction,
+}
+impl ReplayStep
+{
+    //"<time> <action> [<argument>]"
+    pub fn to_line(&self) -> String
+    {
+        let action = match self.action
+        {
+            ReplayAction::MOVE(dx) => format!("move {}", dx),
+            ReplayAction::ROTATE => "rotate".to_owned(),
+            ReplayAction::DROP => "drop".to_owned(),
+            ReplayAction::HOLD => "hold".to_owned(),
+        };
+        //Shortest form reading back as the same f32, so steps land on the same frame
+        format!("{} {}", self.time, action)
+    }
+    pub fn from_words(words : &[&str]) -> Option<ReplayStep>
+    {
+        let (time, rest) = words.split_first()?;
+        let action = match rest
+        {
+            ["move", dx] => ReplayAction::MOVE(dx.parse::<i32>().ok()?),
+            ["rotate"] => ReplayAction::ROTATE,
+            ["drop"] => ReplayAction::DROP,
+            ["hold"] => ReplayAction::HOLD,
+            _ => return None,
+        };
+        Some(ReplayStep { time : time.parse::<f32>().ok()?, action : action })
+    }
+}
+
+//Everything needed to play a game again: its seed, its board, its rules and the inputs that took effect
+#[derive(Clone)]
+pub struct Replay
+{
+    pub seed : u64,
+    pub grid_size : GridSize,
+    pub rules : GameRules,
+    pub element_hash : u64, //Of the elements played with, see ElementTypeList::get_hash
+    pub steps : Vec<ReplayStep>,
+}
+
+impl Replay
+{
+    pub fn new(seed : u64, grid_size : GridSize, rules : &GameRules, element_hash : u64) -> Replay
+    {
+        Replay
+        {
+            seed : seed,
+            grid_size : grid_size,
+            rules : rules.clone(),
+            element_hash : element_hash,
+            steps : vec![],
+        }
+    }
+
+    pub fn get_default_dir() -> PathBuf
+    {
+        storage::get_data_dir().join("replays")
+    }
+
+    //Text file: a small header, then one step per line
+    pub fn save(&self, path : &Path) -> io::Result<()>
+    {
+        let rules = &self.rules;
+        let join = |values : Vec<String>| values.join(" ");
+        let mut lines = vec![
+            HEADER.to_owned(),
+            format!("seed {}", self.seed),
+            format!("size {}", self.grid_size as i32),
+            format!("elements {:016x}", self.element_hash),
+            format!("mode {}", rules.mode.get_name()),
+            format!("spawn {}", rules.spawn.get_name()),
+            format!("placement {}", rules.product_placement.get_name()),
+            format!("next {}", rules.next_pairs),
+            format!("combo {}", join(rules.combo_multipliers.iter().map(|m| m.to_string()).collect())).trim_end().to_owned(),
+            format!("penalty {} {}", rules.garbage_after, rules.garbage_amount),
+            format!("timer {}", join(rules.drop_times.iter().map(|t| t.to_string()).collect())).trim_end().to_owned(),
+        ];
+        for step in &self.steps
+        {
+            lines.push(step.to_line());
+        }
+        storage::write_atomically(path, &(lines.join("\n") + "\n"))
+    }
+
+    pub fn load(path : &Path) -> Result<Replay, String>
+    {
+        let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut lines = contents.lines().enumerate();
+        match lines.next().map(|(_, l)| l.trim())
+        {
+            Some(header) if header == HEADER => (),
+            Some(header) if header.starts_with("metalchemist-replay") =>
+                return Err(format!("{}: unsupported replay version \"{}\"", path.display(), header)),
+            _ => return Err(format!("{}: not a replay file", path.display())),
+        }
+
+        let mut seed = None;
+        let mut grid_size = None;
+        let mut element_hash = None;
+        let mut rules = GameRules::new();
+        let mut found = vec![];
+        let mut steps = vec![];
+        for (n, line) in lines
+        {
+            let error = || format!("{}:{}: invalid line \"{}\"", path.display(), n + 1, line);
+            let words : Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice()
+            {
+                [] => (),
+                ["seed", value] => seed = Some(value.parse::<u64>().map_err(|_| error())?),
+                ["size", value] => grid_size = Some(value.parse::<i32>().ok().and_then(GridSize::from_value).ok_or_else(error)?),
+                ["elements", value] => element_hash = Some(u64::from_str_radix(value, 16).map_err(|_| error())?),
+                [key, values @ ..] if RULE_KEYS.contains(key) =>
+                {
+                    match (*key, values)
+                    {
+                        ("mode", [value]) => rules.mode = GameMode::from_name(value).ok_or_else(error)?,
+                        ("spawn", [value]) => rules.spawn = SpawnKind::from_name(value).ok_or_else(error)?,
+                        ("placement", [value]) => rules.product_placement = ProductPlacement::from_name(value).ok_or_else(error)?,
+                        ("next", [value]) => rules.next_pairs = value.parse::<usize>().map_err(|_| error())?,
+                        ("combo", values) =>
+                        {
+                            rules.combo_multipliers = values.iter().map(|m| m.parse::<u32>()).collect::<Result<Vec<u32>, _>>().map_err(|_| error())?;
+                        },
+                        ("penalty", [after, amount]) =>
+                        {
+                            rules.garbage_after = after.parse().map_err(|_| error())?;
+                            rules.garbage_amount = amount.parse().map_err(|_| error())?;
+                        },
+                        ("timer", values) =>
+                        {
+                            rules.drop_times = values.iter().map(|t| t.parse::<f32>()).collect::<Result<Vec<f32>, _>>().map_err(|_| error())?;
+                        },
+                        _ => return Err(error()),
+                    }
+                    found.push(*key);
+                },
+                _ => steps.push(ReplayStep::from_words(&words).ok_or_else(error)?),
+            }
+        }
+        //Played back with anything else, the game would go its own way
+        if let Some(key) = RULE_KEYS.iter().find(|k| !found.contains(k))
+        {
+            return Err(format!("{}: missing {}", path.display(), key))
+        }
+
+        Ok(Replay
+        {
+            seed : seed.ok_or_else(|| format!("{}: missing seed", path.display()))?,
+            grid_size : grid_size.ok_or_else(|| format!("{}: missing size", path.display()))?,
+            rules : rules,
+            element_hash : element_hash.ok_or_else(|| format!("{}: missing elements", path.display()))?,
+            steps : steps,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn steps_round_trip()
+    {
+        let actions = [ReplayAction::MOVE(-2), ReplayAction::ROTATE, ReplayAction::HOLD, ReplayAction::DROP];
+        for action in actions.iter()
+        {
+            let step = ReplayStep { time : 1.5, action : *action };
+            let line = step.to_line();
+            let words : Vec<&str> = line.split_whitespace().collect();
+            assert_eq!(ReplayStep::from_words(&words).unwrap().action, *action);
+        }
+        assert!(ReplayStep::from_words(&["1.0", "jump"]).is_none());
+    }
+
+    #[test]
+    fn rules_and_times_come_back_exactly()
+    {
+        let mut rules = GameRules::for_mode(GameMode::TIME_ATTACK);
+        rules.combo_multipliers = vec![1, 4];
+        rules.garbage_after = 2;
+        rules.set_timed(true);
+        let mut replay = Replay::new(77, GridSize::FIVE, &rules, 0xDEAD_BEEF);
+        replay.steps.push(ReplayStep { time : 1.0 / 3.0, action : ReplayAction::DROP });
+        replay.steps.push(ReplayStep { time : 12.345_678, action : ReplayAction::MOVE(1) });
+
+        let path = ::std::env::temp_dir().join("metalchemist-test-rules.replay");
+        replay.save(&path).unwrap();
+        let loaded = Replay::load(&path);
+        let _ = fs::remove_file(&path);
+
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.element_hash, 0xDEAD_BEEF);
+        assert!(loaded.rules.mode == GameMode::TIME_ATTACK);
+        assert_eq!(loaded.rules.combo_multipliers, vec![1, 4]);
+        assert_eq!(loaded.rules.garbage_after, 2);
+        assert_eq!(loaded.rules.drop_times, rules.drop_times);
+        assert_eq!(loaded.steps[0].time, 1.0 / 3.0);
+        assert_eq!(loaded.steps[1].time, 12.345_678);
+    }
+
+    #[test]
+    fn older_replays_are_refused()
+    {
+        let path = ::std::env::temp_dir().join("metalchemist-test-v1.replay");
+        fs::write(&path, "metalchemist-replay 1\nseed 5\nsize 6\n0.500 move 1\n1.000 drop\n").unwrap();
+        let older = Replay::load(&path);
+        let _ = fs::remove_file(&path);
+        assert!(older.err().unwrap().contains("unsupported replay version"));
+    }
+}
@@ -0,0 +1,118 @@
+use rand;
+
+//Seedable random source for a board (SplitMix64)
+//Ranges and shuffles are done here too, on top of next_u64, so a given seed gives
+//the same sequence on every platform and rand version: rand only picks fresh seeds
+#[derive(Clone)]
+pub struct SeededRng
+{
+    seed : u64,
+    state : u64,
+}
+
+impl SeededRng
+{
+    pub fn new(seed : u64) -> SeededRng
+    {
+        SeededRng
+        {
+            seed : seed,
+            state : seed,
+        }
+    }
+
+    //Fresh seed for a new game, short enough to be read off the screen
+    pub fn random_seed() -> u64
+    {
+        use rand::Rng;
+        rand::thread_rng().gen_range(0, 100_000_000)
+    }
+
+    //Picks up a sequence where it was left, for saved games
+    pub fn from_state(seed : u64, state : u64) -> SeededRng
+    {
+        SeededRng
+        {
+            seed : seed,
+            state : state,
+        }
+    }
+
+    pub fn get_seed(&self) -> u64
+    {
+        self.seed
+    }
+    pub fn get_state(&self) -> u64
+    {
+        self.state
+    }
+
+    pub fn next_u64(&mut self) -> u64
+    {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+    pub fn next_u32(&mut self) -> u32
+    {
+        (self.next_u64() >> 32) as u32
+    }
+    //Uniform in 0..bound, bound above 0
+    //Draws past the last whole multiple of bound are thrown out so no value comes up more often
+    pub fn below(&mut self, bound : u32) -> u32
+    {
+        let zone = u32::max_value() - u32::max_value() % bound;
+        loop
+        {
+            let value = self.next_u32();
+            if value < zone
+            {
+                return value % bound
+            }
+        }
+    }
+    //Fisher-Yates, from the end
+    pub fn shuffle<T>(&mut self, items : &mut [T])
+    {
+        for i in (1..items.len()).rev()
+        {
+            let j = self.below(i as u32 + 1) as usize;
+            items.swap(i, j);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    //Changing any of these changes every seeded game, replay and save out there
+    #[test]
+    fn seeds_keep_their_sequence()
+    {
+        let mut rng = SeededRng::new(0);
+        let draws : Vec<u64> = (0..4).map(|_| rng.next_u64()).collect();
+        assert_eq!(draws, vec![0xE220_A839_7B1D_CDAF, 0x6E78_9E6A_A1B9_65F4, 0x06C4_5D18_8009_454F, 0xF88B_B8A8_724C_81EC]);
+
+        let mut rng = SeededRng::new(1234);
+        let draws : Vec<u32> = (0..8).map(|_| rng.below(6)).collect();
+        assert_eq!(draws, vec![1, 4, 2, 0, 1, 4, 3, 1]);
+
+        let mut items = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9];
+        SeededRng::new(1234).shuffle(&mut items);
+        assert_eq!(items, [2, 0, 9, 5, 3, 8, 6, 4, 1, 7]);
+    }
+
+    #[test]
+    fn saved_state_picks_up_the_sequence()
+    {
+        let mut rng = SeededRng::new(42);
+        rng.next_u64();
+        let mut resumed = SeededRng::from_state(rng.get_seed(), rng.get_state());
+        assert_eq!(rng.next_u64(), resumed.next_u64());
+        assert_eq!(resumed.get_seed(), 42);
+    }
+}
@@ -0,0 +1,247 @@
+use spawn::SpawnKind;
+
+//Most upcoming pairs a game can show
+pub const MAX_NEXT_PAIRS : usize = 5;
+//Seconds the pair in control may wait before dropping by itself, by speed level, when timed
+const DROP_TIMES : &[f32] = &[10.0, 8.0, 6.5, 5.5, 4.5, 3.5, 3.0, 2.5];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GameMode
+{
+    CLASSIC,
+    TIME_ATTACK, //Most points before the time is up
+    PUZZLE, //Set boards and pairs, with a goal
+}
+impl GameMode
+{
+    //Name used in files
+    pub fn get_name(&self) -> &'static str
+    {
+        match *self
+        {
+            GameMode::CLASSIC => "classic",
+            GameMode::TIME_ATTACK => "time-attack",
+            GameMode::PUZZLE => "puzzle",
+        }
+    }
+    pub fn from_name(name : &str) -> Option<GameMode>
+    {
+        match name
+        {
+            "classic" => Some(GameMode::CLASSIC),
+            "time-attack" => Some(GameMode::TIME_ATTACK),
+            "puzzle" => Some(GameMode::PUZZLE),
+            _ => None,
+        }
+    }
+    //Modes picked for regular games, puzzles are started on their own
+    pub fn next(&self) -> GameMode
+    {
+        match *self
+        {
+            GameMode::CLASSIC => GameMode::TIME_ATTACK,
+            GameMode::TIME_ATTACK | GameMode::PUZZLE => GameMode::CLASSIC,
+        }
+    }
+    //Seconds a game lasts at most, counted from its start
+    pub fn get_time_limit(&self) -> Option<f32>
+    {
+        match *self
+        {
+            GameMode::CLASSIC | GameMode::PUZZLE => None,
+            GameMode::TIME_ATTACK => Some(180.0),
+        }
+    }
+}
+
+//Cell of a reacting group where its product appears
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ProductPlacement
+{
+    LAST_DROPPED, //Where the last dropped pair (or the last product) ended up
+    LOWEST, //Lowest cell, then leftmost
+    CENTROID, //Cell closest to the middle of the group
+}
+impl ProductPlacement
+{
+    //Name used in files
+    pub fn get_name(&self) -> &'static str
+    {
+        match *self
+        {
+            ProductPlacement::LAST_DROPPED => "last-dropped",
+            ProductPlacement::LOWEST => "lowest",
+            ProductPlacement::CENTROID => "centroid",
+        }
+    }
+    pub fn from_name(name : &str) -> Option<ProductPlacement>
+    {
+        match name
+        {
+            "last-dropped" => Some(ProductPlacement::LAST_DROPPED),
+            "lowest" => Some(ProductPlacement::LOWEST),
+            "centroid" => Some(ProductPlacement::CENTROID),
+            _ => None,
+        }
+    }
+    pub fn next(&self) -> ProductPlacement
+    {
+        match *self
+        {
+            ProductPlacement::LAST_DROPPED => ProductPlacement::LOWEST,
+            ProductPlacement::LOWEST => ProductPlacement::CENTROID,
+            ProductPlacement::CENTROID => ProductPlacement::LAST_DROPPED,
+        }
+    }
+}
+
+//Tunable rules of a game, chosen before it starts
+#[derive(Clone)]
+pub struct GameRules
+{
+    pub mode : GameMode,
+    //Score multiplier for each link of a chain: first reaction of a drop, second, and so on
+    //The last one applies to any longer chain
+    pub combo_multipliers : Vec<u32>,
+    pub spawn : SpawnKind,
+    pub product_placement : ProductPlacement,
+    //Drops in a row without a reaction before garbage falls in, 0 for never
+    pub garbage_after : u32,
+    pub garbage_amount : u32,
+    //Upcoming pairs shown, up to MAX_NEXT_PAIRS
+    pub next_pairs : usize,
+    //Seconds before the pair in control drops by itself, for each speed level: none made yet, one, and so on
+    //The last one applies to any higher level, no timer at all if empty
+    pub drop_times : Vec<f32>,
+}
+
+impl GameRules
+{
+    pub fn new() -> GameRules
+    {
+        GameRules::for_mode(GameMode::CLASSIC)
+    }
+    //Default rules of each mode
+    pub fn for_mode(mode : GameMode) -> GameRules
+    {
+        GameRules
+        {
+            mode : mode,
+            combo_multipliers : vec![1, 2, 3, 5, 8],
+            //Time attacks deal every element its share in each bag, puzzles deal their own pairs
+            spawn : match mode
+            {
+                GameMode::CLASSIC => SpawnKind::ANTI_STREAK,
+                GameMode::TIME_ATTACK => SpawnKind::BAG,
+                GameMode::PUZZLE => SpawnKind::WEIGHTED,
+            },
+            product_placement : ProductPlacement::LAST_DROPPED,
+            //Only against the clock do misses bring garbage, puzzles bring their own trouble
+            garbage_after : if mode == GameMode::TIME_ATTACK { 5 } else { 0 },
+            garbage_amount : 3,
+            //More to plan with against the clock, only the next pair to think about in puzzles
+            next_pairs : match mode
+            {
+                GameMode::CLASSIC => 3,
+                GameMode::TIME_ATTACK => 5,
+                GameMode::PUZZLE => 1,
+            },
+            drop_times : vec![],
+        }
+    }
+
+    //chain starts at 1 for the first reaction after a drop
+    pub fn get_combo_multiplier(&self, chain : u32) -> u32
+    {
+        if self.combo_multipliers.is_empty() || chain == 0
+        {
+            return 1
+        }
+        let index = (chain as usize - 1).min(self.combo_multipliers.len() - 1);
+        self.combo_multipliers[index]
+    }
+
+    pub fn is_timed(&self) -> bool
+    {
+        !self.drop_times.is_empty()
+    }
+    pub fn set_timed(&mut self, timed : bool)
+    {
+        self.drop_times = if timed { DROP_TIMES.to_vec() } else { vec![] };
+    }
+    //level counts the elements made so far
+    pub fn get_drop_time(&self, level : u32) -> Option<f32>
+    {
+        if self.drop_times.is_empty()
+        {
+            return None
+        }
+        let index = (level as usize).min(self.drop_times.len() - 1);
+        Some(self.drop_times[index])
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn combo_multiplier_holds_at_its_last_value()
+    {
+        let rules = GameRules::new();
+        assert_eq!(rules.get_combo_multiplier(0), 1);
+        assert_eq!(rules.get_combo_multiplier(1), 1);
+        assert_eq!(rules.get_combo_multiplier(4), 5);
+        assert_eq!(rules.get_combo_multiplier(5), 8);
+        assert_eq!(rules.get_combo_multiplier(40), 8);
+        let mut flat = GameRules::new();
+        flat.combo_multipliers = vec![];
+        assert_eq!(flat.get_combo_multiplier(3), 1);
+    }
+
+    #[test]
+    fn drop_time_speeds_up_then_holds()
+    {
+        let mut rules = GameRules::new();
+        assert!(!rules.is_timed());
+        assert_eq!(rules.get_drop_time(0), None);
+        rules.set_timed(true);
+        assert_eq!(rules.get_drop_time(0), Some(DROP_TIMES[0]));
+        assert_eq!(rules.get_drop_time(1), Some(DROP_TIMES[1]));
+        assert_eq!(rules.get_drop_time(500), DROP_TIMES.last().cloned());
+        rules.set_timed(false);
+        assert!(!rules.is_timed());
+    }
+
+    #[test]
+    fn modes_show_their_own_number_of_pairs()
+    {
+        let counts : Vec<usize> = [GameMode::CLASSIC, GameMode::TIME_ATTACK, GameMode::PUZZLE].iter()
+            .map(|mode| GameRules::for_mode(*mode).next_pairs).collect();
+        assert_eq!(counts, vec![3, 5, 1]);
+        assert!(counts.iter().all(|n| *n <= MAX_NEXT_PAIRS));
+        assert_eq!(GameRules::for_mode(GameMode::CLASSIC).spawn, SpawnKind::ANTI_STREAK);
+        assert_eq!(GameRules::for_mode(GameMode::TIME_ATTACK).spawn, SpawnKind::BAG);
+    }
+
+    #[test]
+    fn names_round_trip()
+    {
+        let mut mode = GameMode::CLASSIC;
+        loop
+        {
+            assert_eq!(GameMode::from_name(mode.get_name()), Some(mode));
+            mode = mode.next();
+            if mode == GameMode::CLASSIC { break }
+        }
+        let mut placement = ProductPlacement::LAST_DROPPED;
+        loop
+        {
+            assert_eq!(ProductPlacement::from_name(placement.get_name()), Some(placement));
+            placement = placement.next();
+            if placement == ProductPlacement::LAST_DROPPED { break }
+        }
+        assert_eq!(GameMode::from_name("nonsense"), None);
+    }
+}
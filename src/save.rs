@@ -0,0 +1,315 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use board::GridSize;
+use element_array::GuideRotation;
+use replay::ReplayStep;
+use rules::{GameMode, GameRules, ProductPlacement};
+use spawn::SpawnKind;
+use storage;
+
+//Older versions are refused rather than misread
+//Version 2: rules and spawn memory in full, exact step times
+const HEADER : &str = "metalchemist-save 2";
+
+//Snapshot of a game in progress
+pub struct SaveGame
+{
+    pub grid_size : GridSize,
+    pub mode : GameMode,
+    pub spawn : SpawnKind,
+    pub spawn_count : u32, //Kept by the spawn policy along with its memory
+    pub spawn_memory : Vec<String>, //By name, like the elements below
+    pub product_placement : ProductPlacement,
+    pub combo_multipliers : Vec<u32>,
+    //Drops without a reaction before garbage comes, and how much
+    pub garbage_after : u32,
+    pub garbage_amount : u32,
+    pub seed : u64,
+    pub rng_state : u64,
+    pub time : f32,
+    pub settling : bool, //Elements were still falling or reacting
+
+    pub score : u64,
+    pub chain : u32,
+    pub longest_chain : u32,
+    pub pairs_dropped : u32,
+    //Seconds each pair may wait by speed level, and what was left for the pair in control; empty when untimed
+    pub drop_times : Vec<f32>,
+    pub drop_time_left : Option<f32>,
+
+    pub garbage_rng_state : Option<u64>, //Missing from older saves
+    pub misses : u32,
+    pub garbage_pending : u32,
+    pub garbage_received : u32,
+    pub garbage_cleared : u32,
+
+    pub guide_pos : i32,
+    pub guide_rot : GuideRotation,
+    //Elements by name, so a save outlives changes to the element order
+    pub unlocks : Vec<String>,
+    pub pair : [Option<String>; 2],
+    pub next : Vec<[Option<String>; 2]>, //Upcoming pairs, as many as the rules show
+    pub hold : [Option<String>; 2],
+    pub hold_used : bool, //Already swapped since the last drop
+    //Px * H + Py = Index, like in ElementArray
+    pub tiles : Vec<Option<String>>,
+    //Tiles holding the last dropped elements or products, by index
+    pub recent : Vec<usize>,
+
+    //Inputs so far, so the game's replay goes on
+    pub steps : Vec<ReplayStep>,
+}
+
+fn element_name(t : &Option<String>) -> &str
+{
+    match *t
+    {
+        Some(ref name) => name,
+        None => "-",
+    }
+}
+fn parse_element(name : &str) -> Option<String>
+{
+    if name == "-"
+    {
+        None
+    }
+    else
+    {
+        Some(name.to_owned())
+    }
+}
+
+impl SaveGame
+{
+    pub fn get_default_path() -> PathBuf
+    {
+        storage::get_data_dir().join("save.txt")
+    }
+
+    pub fn save(&self, path : &Path) -> io::Result<()>
+    {
+        let width = self.grid_size as i32;
+        let height = width + 2;
+
+        let mut lines = vec![
+            HEADER.to_owned(),
+            format!("size {}", width),
+            format!("mode {}", self.mode.get_name()),
+            format!("spawn {} {} {}", self.spawn.get_name(), self.spawn_count, self.spawn_memory.join(" ")).trim_end().to_owned(),
+            format!("placement {}", self.product_placement.get_name()),
+            format!("combo {}", self.combo_multipliers.iter().map(|m| m.to_string()).collect::<Vec<String>>().join(" ")).trim_end().to_owned(),
+            format!("penalty {} {}", self.garbage_after, self.garbage_amount),
+            format!("seed {}", self.seed),
+            format!("rng {}", self.rng_state),
+            format!("time {}", self.time),
+            format!("state {}", if self.settling { "settling" } else { "play" }),
+            format!("score {}", self.score),
+            format!("chain {} {}", self.chain, self.longest_chain),
+            format!("pairs {}", self.pairs_dropped),
+            format!("timer {} {}", self.drop_time_left.map(|t| t.to_string()).unwrap_or("-".to_owned()),
+                self.drop_times.iter().map(|t| t.to_string()).collect::<Vec<String>>().join(" ")).trim_end().to_owned(),
+            format!("garbage {} {} {}", self.garbage_rng_state.unwrap_or(0), self.misses, self.garbage_pending),
+            format!("ash {} {}", self.garbage_received, self.garbage_cleared),
+            format!("guide {} {}", self.guide_pos, self.guide_rot.get_name()),
+            format!("unlocks {}", self.unlocks.join(" ")),
+            format!("pair {} {}", element_name(&self.pair[0]), element_name(&self.pair[1])),
+            format!("hold {} {} {}", element_name(&self.hold[0]), element_name(&self.hold[1]), if self.hold_used { "used" } else { "free" }),
+        ];
+        for next in &self.next
+        {
+            lines.push(format!("next {} {}", element_name(&next[0]), element_name(&next[1])));
+        }
+        //Top row first, so the file looks like the board
+        for py in (0..height).rev()
+        {
+            let row : Vec<&str> = (0..width).map(|px| element_name(&self.tiles[(px * height + py) as usize])).collect();
+            lines.push(format!("row {}", row.join(" ")));
+        }
+        lines.push(format!("recent {}", self.recent.iter().map(|i| i.to_string()).collect::<Vec<String>>().join(" ")).trim_end().to_owned());
+        for step in &self.steps
+        {
+            lines.push(format!("step {}", step.to_line()));
+        }
+        storage::write_atomically(path, &(lines.join("\n") + "\n"))
+    }
+
+    pub fn load(path : &Path) -> Result<SaveGame, String>
+    {
+        let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut lines = contents.lines().enumerate();
+        match lines.next()
+        {
+            Some((_, header)) if header.trim() == HEADER => (),
+            Some((_, header)) if header.starts_with("metalchemist-save") =>
+                return Err(format!("{}: unsupported save version \"{}\"", path.display(), header.trim())),
+            _ => return Err(format!("{}: not a save file", path.display())),
+        }
+
+        let mut save = SaveGame
+        {
+            grid_size : GridSize::SIX,
+            mode : GameMode::CLASSIC,
+            spawn : GameRules::new().spawn,
+            spawn_count : 0,
+            spawn_memory : vec![],
+            product_placement : GameRules::new().product_placement,
+            combo_multipliers : GameRules::new().combo_multipliers,
+            garbage_after : GameRules::new().garbage_after,
+            garbage_amount : GameRules::new().garbage_amount,
+            seed : 0,
+            rng_state : 0,
+            time : 0.0,
+            settling : false,
+            score : 0,
+            chain : 0,
+            longest_chain : 0,
+            pairs_dropped : 0,
+            drop_times : vec![],
+            drop_time_left : None,
+            garbage_rng_state : None,
+            misses : 0,
+            garbage_pending : 0,
+            garbage_received : 0,
+            garbage_cleared : 0,
+            guide_pos : 0,
+            guide_rot : GuideRotation::LEFT,
+            unlocks : vec![],
+            pair : [None, None],
+            next : vec![],
+            hold : [None, None],
+            hold_used : false,
+            tiles : vec![],
+            recent : vec![],
+            steps : vec![],
+        };
+        let mut found_size = false;
+        let mut found_rng = false;
+        let mut rows : Vec<Vec<Option<String>>> = vec![];
+        for (n, line) in lines
+        {
+            let error = || format!("{}:{}: invalid line \"{}\"", path.display(), n + 1, line);
+            let words : Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice()
+            {
+                [] => (),
+                ["size", v] =>
+                {
+                    save.grid_size = v.parse::<i32>().ok().and_then(GridSize::from_value).ok_or_else(error)?;
+                    found_size = true;
+                },
+                ["mode", v] => save.mode = GameMode::from_name(v).ok_or_else(error)?,
+                ["spawn", kind, count, memory @ ..] =>
+                {
+                    save.spawn = SpawnKind::from_name(kind).ok_or_else(error)?;
+                    save.spawn_count = count.parse().map_err(|_| error())?;
+                    save.spawn_memory = memory.iter().map(|n| n.to_string()).collect();
+                },
+                ["placement", v] => save.product_placement = ProductPlacement::from_name(v).ok_or_else(error)?,
+                ["combo", multipliers @ ..] =>
+                {
+                    save.combo_multipliers = multipliers.iter().map(|m| m.parse::<u32>()).collect::<Result<Vec<u32>, _>>().map_err(|_| error())?;
+                },
+                ["penalty", after, amount] =>
+                {
+                    save.garbage_after = after.parse().map_err(|_| error())?;
+                    save.garbage_amount = amount.parse().map_err(|_| error())?;
+                },
+                ["recent", indices @ ..] =>
+                {
+                    save.recent = indices.iter().map(|i| i.parse::<usize>()).collect::<Result<Vec<usize>, _>>().map_err(|_| error())?;
+                },
+                ["seed", v] => save.seed = v.parse().map_err(|_| error())?,
+                ["rng", v] =>
+                {
+                    save.rng_state = v.parse().map_err(|_| error())?;
+                    found_rng = true;
+                },
+                ["time", v] => save.time = v.parse().map_err(|_| error())?,
+                ["state", "play"] => save.settling = false,
+                ["state", "settling"] => save.settling = true,
+                ["score", v] => save.score = v.parse().map_err(|_| error())?,
+                ["chain", c, l] =>
+                {
+                    save.chain = c.parse().map_err(|_| error())?;
+                    save.longest_chain = l.parse().map_err(|_| error())?;
+                },
+                ["pairs", v] => save.pairs_dropped = v.parse().map_err(|_| error())?,
+                ["timer", left, times @ ..] =>
+                {
+                    save.drop_time_left = if *left == "-" { None } else { Some(left.parse().map_err(|_| error())?) };
+                    save.drop_times = times.iter().map(|t| t.parse::<f32>()).collect::<Result<Vec<f32>, _>>().map_err(|_| error())?;
+                },
+                ["garbage", state, misses, pending] =>
+                {
+                    save.garbage_rng_state = Some(state.parse().map_err(|_| error())?);
+                    save.misses = misses.parse().map_err(|_| error())?;
+                    save.garbage_pending = pending.parse().map_err(|_| error())?;
+                },
+                ["ash", received, cleared] =>
+                {
+                    save.garbage_received = received.parse().map_err(|_| error())?;
+                    save.garbage_cleared = cleared.parse().map_err(|_| error())?;
+                },
+                ["guide", pos, rot] =>
+                {
+                    save.guide_pos = pos.parse().map_err(|_| error())?;
+                    save.guide_rot = GuideRotation::from_name(rot).ok_or_else(error)?;
+                },
+                ["unlocks", names @ ..] =>
+                {
+                    save.unlocks = names.iter().map(|n| n.to_string()).collect();
+                },
+                ["pair", a, b] => save.pair = [parse_element(a), parse_element(b)],
+                ["next", a, b] => save.next.push([parse_element(a), parse_element(b)]),
+                ["hold", a, b, used] =>
+                {
+                    save.hold = [parse_element(a), parse_element(b)];
+                    save.hold_used = match *used { "used" => true, "free" => false, _ => return Err(error()) };
+                },
+                ["row", names @ ..] =>
+                {
+                    rows.push(names.iter().map(|n| parse_element(n)).collect());
+                },
+                ["step", rest @ ..] => save.steps.push(ReplayStep::from_words(rest).ok_or_else(error)?),
+                _ => return Err(error()),
+            }
+        }
+        if !found_size || !found_rng
+        {
+            return Err(format!("{}: missing size or rng state", path.display()))
+        }
+
+        //Rows were written top first
+        let width = save.grid_size as i32;
+        let height = width + 2;
+        if rows.len() != height as usize || rows.iter().any(|r| r.len() != width as usize)
+        {
+            return Err(format!("{}: board should be {} rows of {} tiles", path.display(), height, width))
+        }
+        for px in 0..width
+        {
+            for py in 0..height
+            {
+                save.tiles.push(rows[(height - 1 - py) as usize][px as usize].clone());
+            }
+        }
+        if save.recent.iter().any(|i| *i >= save.tiles.len())
+        {
+            return Err(format!("{}: recent tiles outside of the board", path.display()))
+        }
+        Ok(save)
+    }
+
+    //Declined or finished games should not be offered again
+    pub fn delete(path : &Path)
+    {
+        match fs::remove_file(path)
+        {
+            Ok(()) => (),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => (),
+            Err(e) => storage::report(&format!("Could not remove save {}: {}", path.display(), e)),
+        }
+    }
+}
@@ -0,0 +1,108 @@
+//Reader for the human-editable data files in content/:
+//
+//  # comment
+//  [section name]
+//  key = value
+//
+//Errors point at "<source>:<line>" so they can be fixed by hand.
+
+pub struct Entry
+{
+    pub key : String,
+    pub value : String,
+    pub line : usize,
+}
+
+pub struct Section
+{
+    pub name : String,
+    pub line : usize,
+    pub entries : Vec<Entry>,
+}
+
+impl Section
+{
+    pub fn get(&self, key : &str) -> Option<&Entry>
+    {
+        self.entries.iter().find(|e| e.key == key)
+    }
+    //Same as get, with an error naming the section when missing
+    pub fn require(&self, key : &str, source : &str) -> Result<&Entry, String>
+    {
+        self.get(key).ok_or_else(|| format!("{}:{}: [{}] is missing \"{}\"", source, self.line, self.name, key))
+    }
+    //Fails on keys outside of the known ones, and on repeated keys
+    pub fn check_keys(&self, known : &[&str], source : &str) -> Result<(), String>
+    {
+        for (i, entry) in self.entries.iter().enumerate()
+        {
+            if !known.contains(&entry.key.as_str())
+            {
+                return Err(format!("{}:{}: unknown key \"{}\" in [{}], expected one of: {}", source, entry.line, entry.key, self.name, known.join(", ")))
+            }
+            if self.entries[..i].iter().any(|e| e.key == entry.key)
+            {
+                return Err(format!("{}:{}: \"{}\" is set twice in [{}]", source, entry.line, entry.key, self.name))
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Entry
+{
+    pub fn error(&self, source : &str, message : &str) -> String
+    {
+        format!("{}:{}: {}: {}", source, self.line, self.key, message)
+    }
+    pub fn parse<T : ::std::str::FromStr>(&self, source : &str) -> Result<T, String>
+    {
+        self.value.parse::<T>().map_err(|_| self.error(source, &format!("invalid value \"{}\"", self.value)))
+    }
+    //Whitespace separated values
+    pub fn words(&self) -> Vec<&str>
+    {
+        self.value.split_whitespace().collect()
+    }
+}
+
+pub fn parse(text : &str, source : &str) -> Result<Vec<Section>, String>
+{
+    let mut sections : Vec<Section> = vec![];
+    for (n, raw) in text.lines().enumerate()
+    {
+        let line = n + 1;
+        let content = match raw.find('#')
+        {
+            Some(i) => &raw[..i],
+            None => raw,
+        }.trim();
+        if content.is_empty()
+        {
+            continue
+        }
+
+        if content.starts_with('[')
+        {
+            if !content.ends_with(']') || content.len() < 3
+            {
+                return Err(format!("{}:{}: expected a section name like [name], found \"{}\"", source, line, content))
+            }
+            sections.push(Section { name : content[1..content.len() - 1].trim().to_lowercase(), line : line, entries : vec![] });
+        }
+        else
+        {
+            let (key, value) = match content.find('=')
+            {
+                Some(i) => (content[..i].trim(), content[i + 1..].trim()),
+                None => return Err(format!("{}:{}: expected \"key = value\", found \"{}\"", source, line, content)),
+            };
+            match sections.last_mut()
+            {
+                Some(section) => section.entries.push(Entry { key : key.to_lowercase(), value : value.to_owned(), line : line }),
+                None => return Err(format!("{}:{}: \"{}\" is outside of any [section]", source, line, key)),
+            }
+        }
+    }
+    Ok(sections)
+}
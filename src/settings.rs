@@ -0,0 +1,104 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use sections;
+use storage;
+
+//Player preferences kept between runs, in the same format as the content files:
+//
+//  [display]
+//  ghost = true
+//  preview = true
+pub struct Settings
+{
+    path : PathBuf,
+    pub show_ghost : bool, //Where the pair in control would land
+    pub show_preview : bool, //What it would make react
+}
+
+impl Settings
+{
+    pub fn get_default_path() -> PathBuf
+    {
+        storage::get_data_dir().join("settings.txt")
+    }
+
+    //A missing file gives the defaults, anything unreadable in it is skipped
+    pub fn load(path : PathBuf) -> Settings
+    {
+        let mut settings = Settings { path : path, show_ghost : true, show_preview : true };
+        let text = match fs::read_to_string(&settings.path)
+        {
+            Ok(text) => text,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return settings,
+            Err(e) =>
+            {
+                storage::report(&format!("Could not read settings {}: {}", settings.path.display(), e));
+                return settings
+            },
+        };
+        let source = settings.path.display().to_string();
+        match sections::parse(&text, &source)
+        {
+            Ok(found) =>
+            {
+                for section in found.iter().filter(|s| s.name == "display")
+                {
+                    for (key, value) in vec![("ghost", &mut settings.show_ghost), ("preview", &mut settings.show_preview)]
+                    {
+                        if let Some(entry) = section.get(key)
+                        {
+                            match entry.parse::<bool>(&source)
+                            {
+                                Ok(v) => *value = v,
+                                Err(e) => storage::report(&format!("Skipped setting {}", e)),
+                            }
+                        }
+                    }
+                }
+            },
+            Err(e) => storage::report(&format!("Ignoring settings {}", e)),
+        }
+        settings
+    }
+
+    pub fn save(&self) -> io::Result<()>
+    {
+        let contents = format!("[display]\nghost = {}\npreview = {}\n", self.show_ghost, self.show_preview);
+        storage::write_atomically(&self.path, &contents)
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn settings_round_trip()
+    {
+        let path = env::temp_dir().join("metalchemist-test-settings.txt");
+        let mut settings = Settings::load(path.clone());
+        settings.show_ghost = false;
+        settings.save().unwrap();
+        let loaded = Settings::load(path.clone());
+        let _ = fs::remove_file(&path);
+        assert!(!loaded.show_ghost);
+        assert!(loaded.show_preview);
+    }
+
+    #[test]
+    fn unreadable_settings_keep_their_defaults()
+    {
+        let path = env::temp_dir().join("metalchemist-test-bad-settings.txt");
+        let _ = fs::remove_file(&path);
+        let missing = Settings::load(path.clone());
+        fs::write(&path, "[display]\nghost = maybe\npreview = false\n").unwrap();
+        let damaged = Settings::load(path.clone());
+        let _ = fs::remove_file(&path);
+        assert!(missing.show_ghost && missing.show_preview);
+        assert!(damaged.show_ghost);
+        assert!(!damaged.show_preview);
+    }
+}
@@ -1,9 +1,11 @@
 extern crate glium;
 mod basic_shader;
+mod tinted_shader;
 
 pub struct ProgramManager
 {
     prog_basic : glium::Program,
+    prog_tinted : glium::Program,
 }
 
 impl ProgramManager
@@ -14,14 +16,16 @@ impl ProgramManager
         ProgramManager
         { 
             prog_basic : basic_shader::get_shader(display),
+            prog_tinted : tinted_shader::get_shader(display),
         }
     }
 
-    pub fn get_program(self, sp: ShaderProgram) -> glium::Program
+    pub fn get_program(&self, sp: ShaderProgram) -> &glium::Program
     {
         match sp
         {
-            ShaderProgram::Basic => self.prog_basic,
+            ShaderProgram::Basic => &self.prog_basic,
+            ShaderProgram::Tinted => &self.prog_tinted,
             //_ => panic!(), //THERE IS NO SHADER HERE!!
         }
     }
@@ -30,4 +34,5 @@ impl ProgramManager
 pub enum ShaderProgram
 {
     Basic,
+    Tinted, //Needs a "tint" color uniform
 }
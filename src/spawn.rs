@@ -0,0 +1,418 @@
+use element::{ElementType, ElementTypeList};
+use rng::SeededRng;
+
+//Ways to pick the elements of the next pairs, chosen by the game rules
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SpawnKind
+{
+    WEIGHTED,
+    BAG,
+    ANTI_STREAK,
+}
+impl SpawnKind
+{
+    //Name used in files
+    pub fn get_name(&self) -> &'static str
+    {
+        match *self
+        {
+            SpawnKind::WEIGHTED => "weighted",
+            SpawnKind::BAG => "bag",
+            SpawnKind::ANTI_STREAK => "anti-streak",
+        }
+    }
+    pub fn from_name(name : &str) -> Option<SpawnKind>
+    {
+        match name
+        {
+            "weighted" => Some(SpawnKind::WEIGHTED),
+            "bag" => Some(SpawnKind::BAG),
+            "anti-streak" => Some(SpawnKind::ANTI_STREAK),
+            _ => None,
+        }
+    }
+    pub fn create(&self) -> Box<dyn SpawnPolicy>
+    {
+        match *self
+        {
+            SpawnKind::WEIGHTED => Box::new(WeightedSpawn),
+            SpawnKind::BAG => Box::new(BagSpawn { bag : vec![] }),
+            SpawnKind::ANTI_STREAK => Box::new(AntiStreakSpawn { history : vec![], credit : 0 }),
+        }
+    }
+}
+
+pub trait SpawnPolicy
+{
+    //unlocks always holds at least one element that can spawn
+    fn next(&mut self, unlocks : &Vec<ElementType>, data : &ElementTypeList, rng : &mut SeededRng) -> ElementType;
+    //Whatever the policy remembers between pairs, kept in saved games
+    fn get_memory(&self) -> Vec<ElementType>;
+    fn set_memory(&mut self, memory : Vec<ElementType>);
+    //Number kept along with the memory, for policies that count something
+    fn get_count(&self) -> u32
+    {
+        0
+    }
+    fn set_count(&mut self, _count : u32)
+    {
+    }
+    fn box_clone(&self) -> Box<dyn SpawnPolicy>;
+    //Policies with a set number of elements to deal, once they are all dealt
+    fn is_exhausted(&self) -> bool
+    {
+        false
+    }
+}
+impl Clone for Box<dyn SpawnPolicy>
+{
+    fn clone(&self) -> Box<dyn SpawnPolicy>
+    {
+        self.box_clone()
+    }
+}
+
+//Each draw on its own, in proportion to the elements' weights
+#[derive(Clone)]
+pub struct WeightedSpawn;
+
+impl WeightedSpawn
+{
+    fn draw(unlocks : &Vec<ElementType>, data : &ElementTypeList, rng : &mut SeededRng) -> ElementType
+    {
+        let mut weights : Vec<u32> = Vec::with_capacity(unlocks.len());
+        let mut total = 0;
+        for i in 0..unlocks.len()
+        {
+            total += data.get_data(&unlocks[i]).get_weight();
+            weights.push(data.get_data(&unlocks[i]).get_weight());
+        }
+        let random = rng.below(total.max(1));
+
+        let mut temp = 0;
+        for i in 0..weights.len()
+        {
+            temp += weights[i];
+            if temp > random
+            {
+                return unlocks[i as usize]
+            }
+        }
+        //Only reached when nothing has any weight
+        unlocks[0]
+    }
+}
+impl SpawnPolicy for WeightedSpawn
+{
+    fn next(&mut self, unlocks : &Vec<ElementType>, data : &ElementTypeList, rng : &mut SeededRng) -> ElementType
+    {
+        WeightedSpawn::draw(unlocks, data, rng)
+    }
+    fn get_memory(&self) -> Vec<ElementType>
+    {
+        vec![]
+    }
+    fn set_memory(&mut self, _memory : Vec<ElementType>)
+    {
+    }
+    fn box_clone(&self) -> Box<dyn SpawnPolicy>
+    {
+        Box::new(self.clone())
+    }
+}
+
+//Shuffled bag of the unlocked elements, refilled once empty
+//Weights become copies in the bag, reduced by their common divisor; one of each if none has any weight
+#[derive(Clone)]
+pub struct BagSpawn
+{
+    bag : Vec<ElementType>, //Drawn from the end
+}
+impl BagSpawn
+{
+    fn refill(&mut self, unlocks : &Vec<ElementType>, data : &ElementTypeList, rng : &mut SeededRng)
+    {
+        fn gcd(a : u32, b : u32) -> u32 { if b == 0 { a } else { gcd(b, a % b) } }
+        let divisor = unlocks.iter().map(|t| data.get_data(t).get_weight()).fold(0, gcd).max(1);
+        for t in unlocks
+        {
+            for _ in 0..data.get_data(t).get_weight() / divisor
+            {
+                self.bag.push(*t);
+            }
+        }
+        if self.bag.is_empty()
+        {
+            self.bag = unlocks.clone();
+        }
+        rng.shuffle(&mut self.bag);
+    }
+}
+impl SpawnPolicy for BagSpawn
+{
+    fn next(&mut self, unlocks : &Vec<ElementType>, data : &ElementTypeList, rng : &mut SeededRng) -> ElementType
+    {
+        if self.bag.is_empty()
+        {
+            self.refill(unlocks, data, rng);
+        }
+        self.bag.pop().unwrap()
+    }
+    fn get_memory(&self) -> Vec<ElementType>
+    {
+        self.bag.clone()
+    }
+    fn set_memory(&mut self, memory : Vec<ElementType>)
+    {
+        self.bag = memory;
+    }
+    fn box_clone(&self) -> Box<dyn SpawnPolicy>
+    {
+        Box::new(self.clone())
+    }
+}
+
+//Weighted draws that never make a streak longer than MAX_STREAK,
+//and forced for an element missing for much longer than its weight would have it
+#[derive(Clone)]
+pub struct AntiStreakSpawn
+{
+    history : Vec<ElementType>, //Most recent last
+    credit : u32, //Forced draws allowed: each weighted draw earns one, each forced draw spends one
+}
+impl AntiStreakSpawn
+{
+    const MAX_STREAK : usize = 2; //Same element in a row, the next draw is among the others
+    const DROUGHT : u32 = 3; //Draws an element is expected in, times this, before it is forced
+    const HISTORY : usize = 48; //Elements expected less often than that are never forced
+
+    //Draws an element of weight w can be missing for before it is forced
+    fn get_window(weight : u32, total : u32) -> usize
+    {
+        (AntiStreakSpawn::DROUGHT * total / weight.max(1)) as usize
+    }
+    //Element missing for the longest compared to its window, once past it
+    //Elements unlocked since the history began count as missing from it, so they show up soon after being made
+    fn get_overdue(&self, unlocks : &Vec<ElementType>, data : &ElementTypeList) -> Option<ElementType>
+    {
+        let total : u32 = unlocks.iter().map(|t| data.get_data(t).get_weight()).sum();
+        let mut overdue : Option<(ElementType, u64)> = None;
+        for t in unlocks
+        {
+            let weight = data.get_data(t).get_weight();
+            let window = AntiStreakSpawn::get_window(weight, total);
+            if weight == 0 || window > AntiStreakSpawn::HISTORY || self.history.len() < window
+            {
+                continue
+            }
+            let missing = self.history.iter().rev().position(|h| h == t).unwrap_or(self.history.len());
+            //Missing draws over window, compared without dividing
+            let lateness = missing as u64 * weight as u64;
+            if missing >= window && overdue.map_or(true, |(_, most)| lateness > most)
+            {
+                overdue = Some((*t, lateness));
+            }
+        }
+        overdue.map(|(t, _)| t)
+    }
+    //Element drawn MAX_STREAK times in a row just now
+    fn get_streak(&self) -> Option<ElementType>
+    {
+        let last = *self.history.last()?;
+        if self.history.len() >= AntiStreakSpawn::MAX_STREAK
+            && self.history.iter().rev().take(AntiStreakSpawn::MAX_STREAK).all(|h| *h == last)
+        {
+            Some(last)
+        }
+        else
+        {
+            None
+        }
+    }
+}
+impl SpawnPolicy for AntiStreakSpawn
+{
+    fn next(&mut self, unlocks : &Vec<ElementType>, data : &ElementTypeList, rng : &mut SeededRng) -> ElementType
+    {
+        let forced = if self.credit > 0 { self.get_overdue(unlocks, data) } else { None };
+        let t = match forced
+        {
+            Some(t) =>
+            {
+                self.credit -= 1;
+                t
+            },
+            None =>
+            {
+                //Among the others after a streak, unless nothing else can spawn
+                let others : Vec<ElementType> = match self.get_streak()
+                {
+                    Some(last) => unlocks.iter().cloned().filter(|t| *t != last).collect(),
+                    None => vec![],
+                };
+                self.credit = (self.credit + 1).min(AntiStreakSpawn::HISTORY as u32);
+                if others.iter().any(|t| data.get_data(t).get_weight() > 0)
+                {
+                    WeightedSpawn::draw(&others, data, rng)
+                }
+                else
+                {
+                    WeightedSpawn::draw(unlocks, data, rng)
+                }
+            },
+        };
+
+        self.history.push(t);
+        if self.history.len() > AntiStreakSpawn::HISTORY
+        {
+            self.history.remove(0);
+        }
+        t
+    }
+    fn get_memory(&self) -> Vec<ElementType>
+    {
+        self.history.clone()
+    }
+    fn set_memory(&mut self, memory : Vec<ElementType>)
+    {
+        self.history = memory;
+    }
+    fn get_count(&self) -> u32
+    {
+        self.credit
+    }
+    fn set_count(&mut self, count : u32)
+    {
+        self.credit = count;
+    }
+    fn box_clone(&self) -> Box<dyn SpawnPolicy>
+    {
+        Box::new(self.clone())
+    }
+}
+
+//Deals a set sequence, e.g. the pairs of a puzzle; nothing to choose from
+#[derive(Clone)]
+pub struct FixedSpawn
+{
+    sequence : Vec<ElementType>, //Dealt from the end
+}
+impl FixedSpawn
+{
+    pub fn new(sequence : Vec<ElementType>) -> FixedSpawn
+    {
+        FixedSpawn { sequence : sequence.into_iter().rev().collect() }
+    }
+}
+impl SpawnPolicy for FixedSpawn
+{
+    fn next(&mut self, unlocks : &Vec<ElementType>, _data : &ElementTypeList, _rng : &mut SeededRng) -> ElementType
+    {
+        //Only asked for more than it has by mistake
+        self.sequence.pop().unwrap_or(unlocks[0])
+    }
+    fn get_memory(&self) -> Vec<ElementType>
+    {
+        self.sequence.clone()
+    }
+    fn set_memory(&mut self, memory : Vec<ElementType>)
+    {
+        self.sequence = memory;
+    }
+    fn box_clone(&self) -> Box<dyn SpawnPolicy>
+    {
+        Box::new(self.clone())
+    }
+    fn is_exhausted(&self) -> bool
+    {
+        self.sequence.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn draw_many(unlocks : &Vec<ElementType>, data : &ElementTypeList, count : usize) -> Vec<ElementType>
+    {
+        let mut spawner = SpawnKind::ANTI_STREAK.create();
+        let mut rng = SeededRng::new(2024);
+        (0..count).map(|_| spawner.next(unlocks, data, &mut rng)).collect()
+    }
+    fn named(data : &ElementTypeList, names : &[&str]) -> Vec<ElementType>
+    {
+        names.iter().map(|n| data.from_name(n).unwrap()).collect()
+    }
+    fn get_longest_run(draws : &Vec<ElementType>) -> usize
+    {
+        let mut longest = 0;
+        let mut run = 0;
+        for i in 0..draws.len()
+        {
+            run = if i > 0 && draws[i] == draws[i - 1] { run + 1 } else { 1 };
+            longest = longest.max(run);
+        }
+        longest
+    }
+
+    #[test]
+    fn bag_deals_each_weight_once_per_refill()
+    {
+        let data = ElementTypeList::new();
+        let unlocks = named(&data, &["air", "salt", "sulfur"]);
+        let mut spawner = SpawnKind::BAG.create();
+        let mut rng = SeededRng::new(2024);
+        //20, 40 and 24 over their common divisor of 4
+        let draws : Vec<ElementType> = (0..21 * 3).map(|_| spawner.next(&unlocks, &data, &mut rng)).collect();
+        for bag in draws.chunks(21)
+        {
+            let counts : Vec<usize> = unlocks.iter().map(|t| bag.iter().filter(|d| *d == t).count()).collect();
+            assert_eq!(counts, vec![5, 10, 6]);
+        }
+    }
+
+    #[test]
+    fn bag_without_weights_deals_one_of_each()
+    {
+        let data = ElementTypeList::new();
+        //Gold has no weight, it never spawns by itself
+        let unlocks = named(&data, &["gold"]);
+        let mut spawner = SpawnKind::BAG.create();
+        let mut rng = SeededRng::new(7);
+        let draws : Vec<ElementType> = (0..3).map(|_| spawner.next(&unlocks, &data, &mut rng)).collect();
+        assert_eq!(draws, named(&data, &["gold", "gold", "gold"]));
+    }
+
+    #[test]
+    fn anti_streak_keeps_to_the_weights()
+    {
+        let data = ElementTypeList::new();
+        let unlocks : Vec<ElementType> = data.get_all().into_iter().filter(|t| Some(*t) != data.get_garbage()).collect();
+        let total : u32 = unlocks.iter().map(|t| data.get_data(t).get_weight()).sum();
+        let draws = draw_many(&unlocks, &data, 12000);
+        for t in &unlocks
+        {
+            let expected = 12000.0 * data.get_data(t).get_weight() as f32 / total as f32;
+            let count = draws.iter().filter(|d| *d == t).count() as f32;
+            assert!((count - expected).abs() <= expected * 0.1 + 10.0, "{} came {} times, expected {}", data.get_name(t), count, expected);
+        }
+        assert!(get_longest_run(&draws) <= AntiStreakSpawn::MAX_STREAK);
+    }
+
+    #[test]
+    fn anti_streak_forces_missing_starters()
+    {
+        let data = ElementTypeList::new();
+        let unlocks = data.get_starting().clone();
+        let draws = draw_many(&unlocks, &data, 4000);
+        assert!(get_longest_run(&draws) <= AntiStreakSpawn::MAX_STREAK);
+        //Each starter is expected every 4 draws, and forced after 12 without it
+        for t in &unlocks
+        {
+            let seen : Vec<usize> = (0..draws.len()).filter(|i| draws[*i] == *t).collect();
+            let longest_gap = seen.windows(2).map(|w| w[1] - w[0] - 1).max().unwrap();
+            assert!(longest_gap <= 13, "{} missing for {} draws", data.get_name(t), longest_gap);
+        }
+    }
+}
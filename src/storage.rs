@@ -0,0 +1,73 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+//Folder for the files kept between runs, in the user's data directory
+pub fn get_data_dir() -> PathBuf
+{
+    let base = if let Some(dir) = env::var_os("XDG_DATA_HOME")
+    {
+        PathBuf::from(dir)
+    }
+    else if let Some(dir) = env::var_os("APPDATA") //Windows
+    {
+        PathBuf::from(dir)
+    }
+    else if let Some(home) = env::var_os("HOME")
+    {
+        PathBuf::from(home).join(".local").join("share")
+    }
+    else
+    {
+        PathBuf::from(".")
+    };
+    base.join("metalchemist")
+}
+
+//Replaces a file kept between runs, its folder made if needed
+//Written aside first, so a crash never leaves half a file behind
+pub fn write_atomically(path : &Path, contents : &str) -> io::Result<()>
+{
+    if let Some(dir) = path.parent()
+    {
+        fs::create_dir_all(dir)?;
+    }
+    let temp = path.with_extension("tmp");
+    fs::write(&temp, contents)?;
+    fs::rename(&temp, path)
+}
+
+//Seconds since 1970-01-01 UTC
+pub fn get_timestamp() -> u64
+{
+    match SystemTime::now().duration_since(UNIX_EPOCH)
+    {
+        Ok(duration) => duration.as_secs(),
+        Err(_) => 0,
+    }
+}
+
+//YYYY-MM-DD for a timestamp, in UTC
+pub fn format_date(timestamp : u64) -> String
+{
+    //Days to civil date, from Howard Hinnant's algorithm
+    let z = (timestamp / 86400) as i64 + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+//Problems the game gets past on its own (unreadable files, failed saves, skipped lines)
+//and where files were written; kept off the regular output, on stderr
+pub fn report(message : &str)
+{
+    eprintln!("metalchemist: {}", message);
+}
@@ -0,0 +1,124 @@
+extern crate glium;
+
+use glium::texture::Texture2d;
+use vertex::{TextureVertex, Square};
+
+//Tiny 3x5 pixel font, built into a texture at startup
+//Each row is 3 bits wide, most significant bit on the left
+const GLYPHS : &[(char, [u8; 5])] = &[
+    ('0', [0b111, 0b101, 0b101, 0b101, 0b111]),
+    ('1', [0b010, 0b110, 0b010, 0b010, 0b111]),
+    ('2', [0b111, 0b001, 0b111, 0b100, 0b111]),
+    ('3', [0b111, 0b001, 0b111, 0b001, 0b111]),
+    ('4', [0b101, 0b101, 0b111, 0b001, 0b001]),
+    ('5', [0b111, 0b100, 0b111, 0b001, 0b111]),
+    ('6', [0b111, 0b100, 0b111, 0b101, 0b111]),
+    ('7', [0b111, 0b001, 0b001, 0b010, 0b010]),
+    ('8', [0b111, 0b101, 0b111, 0b101, 0b111]),
+    ('9', [0b111, 0b101, 0b111, 0b001, 0b111]),
+    ('A', [0b010, 0b101, 0b111, 0b101, 0b101]),
+    ('B', [0b110, 0b101, 0b110, 0b101, 0b110]),
+    ('C', [0b011, 0b100, 0b100, 0b100, 0b011]),
+    ('D', [0b110, 0b101, 0b101, 0b101, 0b110]),
+    ('E', [0b111, 0b100, 0b110, 0b100, 0b111]),
+    ('F', [0b111, 0b100, 0b110, 0b100, 0b100]),
+    ('G', [0b011, 0b100, 0b101, 0b101, 0b011]),
+    ('H', [0b101, 0b101, 0b111, 0b101, 0b101]),
+    ('I', [0b111, 0b010, 0b010, 0b010, 0b111]),
+    ('J', [0b001, 0b001, 0b001, 0b101, 0b010]),
+    ('K', [0b101, 0b101, 0b110, 0b101, 0b101]),
+    ('L', [0b100, 0b100, 0b100, 0b100, 0b111]),
+    ('M', [0b101, 0b111, 0b111, 0b101, 0b101]),
+    ('N', [0b110, 0b101, 0b101, 0b101, 0b101]),
+    ('O', [0b010, 0b101, 0b101, 0b101, 0b010]),
+    ('P', [0b110, 0b101, 0b110, 0b100, 0b100]),
+    ('Q', [0b010, 0b101, 0b101, 0b110, 0b011]),
+    ('R', [0b110, 0b101, 0b110, 0b101, 0b101]),
+    ('S', [0b011, 0b100, 0b010, 0b001, 0b110]),
+    ('T', [0b111, 0b010, 0b010, 0b010, 0b010]),
+    ('U', [0b101, 0b101, 0b101, 0b101, 0b111]),
+    ('V', [0b101, 0b101, 0b101, 0b101, 0b010]),
+    ('W', [0b101, 0b101, 0b111, 0b111, 0b101]),
+    ('X', [0b101, 0b101, 0b010, 0b101, 0b101]),
+    ('Y', [0b101, 0b101, 0b010, 0b010, 0b010]),
+    ('Z', [0b111, 0b001, 0b010, 0b100, 0b111]),
+    (':', [0b000, 0b010, 0b000, 0b010, 0b000]),
+    ('-', [0b000, 0b000, 0b111, 0b000, 0b000]),
+    ('+', [0b000, 0b010, 0b111, 0b010, 0b000]),
+    ('.', [0b000, 0b000, 0b000, 0b000, 0b010]),
+    ('/', [0b001, 0b001, 0b010, 0b100, 0b100]),
+    ('!', [0b010, 0b010, 0b010, 0b000, 0b010]),
+    ('?', [0b111, 0b001, 0b010, 0b000, 0b010]),
+    ('<', [0b001, 0b010, 0b100, 0b010, 0b001]),
+    ('>', [0b100, 0b010, 0b001, 0b010, 0b100]),
+];
+
+//Atlas layout, in pixels
+const CELL_W : u32 = 4;
+const CELL_H : u32 = 6;
+const COLUMNS : u32 = 16;
+const ROWS : u32 = 4;
+
+pub fn get_font(display: &glium::Display) -> Texture2d
+{
+    let tex_w = CELL_W * COLUMNS;
+    let tex_h = CELL_H * ROWS;
+    let mut pixels = vec![0u8; (tex_w * tex_h * 4) as usize];
+    for (i, &(_, rows)) in GLYPHS.iter().enumerate()
+    {
+        let cell_x = (i as u32 % COLUMNS) * CELL_W;
+        let cell_y = (i as u32 / COLUMNS) * CELL_H;
+        for (r, bits) in rows.iter().enumerate()
+        {
+            for c in 0..3
+            {
+                if bits & (0b100 >> c) != 0
+                {
+                    //Texture rows go from the bottom up
+                    let px = cell_x + c;
+                    let py = tex_h - 1 - (cell_y + r as u32);
+                    let index = ((py * tex_w + px) * 4) as usize;
+                    pixels[index..index + 4].copy_from_slice(&[255, 255, 255, 255]);
+                }
+            }
+        }
+    }
+    let image = glium::texture::RawImage2d::from_raw_rgba(pixels, (tex_w, tex_h));
+    Texture2d::new(display, image).unwrap()
+}
+
+//Vertices for a line of text; (x, y) is the bottom-left corner, size is the height of a font pixel
+pub fn get_text_vertices(text: &str, x: f32, y: f32, size: f32) -> Vec<TextureVertex>
+{
+    let depth = 2.0;
+    let tex_w = (CELL_W * COLUMNS) as f32;
+    let tex_h = (CELL_H * ROWS) as f32;
+    let w = 3.0 * size;
+    let h = 5.0 * size;
+
+    let mut texvec = vec![];
+    let mut cursor = x;
+    for ch in text.chars()
+    {
+        let ch = ch.to_ascii_uppercase();
+        if ch != ' '
+        {
+            let index = GLYPHS.iter().position(|&(g, _)| g == ch)
+                .unwrap_or_else(|| GLYPHS.iter().position(|&(g, _)| g == '?').unwrap()) as u32;
+            let tx = ((index % COLUMNS) * CELL_W) as f32 / tex_w;
+            let ty = (tex_h - ((index / COLUMNS) * CELL_H) as f32) / tex_h;
+            let tw = 3.0 / tex_w;
+            let th = 5.0 / tex_h;
+
+            let sq = Square {
+                top_left:     TextureVertex { position: [  cursor,  y+h, depth], tex_coords: [ tx, ty ] },
+                top_right:    TextureVertex { position: [cursor+w,  y+h, depth], tex_coords: [ tx+tw, ty ] },
+                bottom_left:  TextureVertex { position: [  cursor,   y,  depth], tex_coords: [ tx, ty-th ] },
+                bottom_right: TextureVertex { position: [cursor+w,   y,  depth], tex_coords: [ tx+tw, ty-th ] },
+            };
+            texvec.extend(sq.get_vec());
+        }
+        cursor += 4.0 * size;
+    }
+    texvec
+}
@@ -1,13 +1,4 @@
-extern crate glium;
-
-use camera;
-
-pub trait Drawable 
-{
-    fn draw(&self, &mut glium::Frame, &camera::Camera);
-}
-
-pub trait Updatable 
+pub trait Updatable
 {
     fn update(&mut self, f32);
-}
\ No newline at end of file
+}
@@ -0,0 +1,169 @@
+extern crate glium;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+use glium::glutin::VirtualKeyCode;
+use drawable::Drawable;
+use camera::Camera;
+use grid::Grid;
+use inputs::Inputs;
+use shaders::ProgramManager;
+use metalchemist::traits::Updatable;
+use metalchemist::board::GridSize;
+use metalchemist::element::ElementTypeList;
+use metalchemist::rng::SeededRng;
+use metalchemist::highscores::HighScores;
+use metalchemist::settings::Settings;
+
+//Rotate, left, right, drop and hold, for each player
+const KEYS : [[VirtualKeyCode; 5]; 2] =
+[
+    [VirtualKeyCode::W, VirtualKeyCode::A, VirtualKeyCode::D, VirtualKeyCode::S, VirtualKeyCode::Q],
+    [VirtualKeyCode::Up, VirtualKeyCode::Left, VirtualKeyCode::Right, VirtualKeyCode::Down, VirtualKeyCode::RShift],
+];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum VersusResult
+{
+    WON,
+    LOST,
+    DRAW, //Both topped out at once
+}
+
+//Two grids side by side on the same elements: garbage earned on one falls on the other,
+//the first to top out loses
+pub struct Versus<'a>
+{
+    grids : [Grid<'a>; 2],
+    inputs : [Inputs; 2], //Remembers previous button states, per player
+    decided : bool,
+}
+
+impl<'a> Versus<'a>
+{
+    //Scores and settings are the ones of the main grid, not loaded again for each player
+    pub fn new(disp : &'a glium::Display, programs : &'a ProgramManager, data : &ElementTypeList,
+               high_scores : &Rc<RefCell<HighScores>>, settings : &Rc<RefCell<Settings>>) -> Versus<'a>
+    {
+        let mut grids = [Grid::new(disp, programs, high_scores.clone(), settings.clone()),
+                         Grid::new(disp, programs, high_scores.clone(), settings.clone())];
+        //Half size, each on its half of the screen
+        for (player, grid) in grids.iter_mut().enumerate()
+        {
+            grid.set_element_data(data.clone());
+            grid.set_player(player);
+            grid.set_placement(if player == 0 { -6.0 } else { 6.0 }, 0.0, 0.5);
+        }
+        let mut versus = Versus
+        {
+            grids : grids,
+            inputs : [Inputs { rotate : false, left : false, right : false, drop : false },
+                      Inputs { rotate : false, left : false, right : false, drop : false }],
+            decided : false,
+        };
+        versus.start();
+        versus
+    }
+    //Both players get the same elements
+    fn start(&mut self)
+    {
+        let seed = SeededRng::random_seed();
+        for grid in self.grids.iter_mut()
+        {
+            grid.reset_grid_with_seed(GridSize::SIX, seed);
+            grid.set_versus_result(None);
+        }
+        self.decided = false;
+    }
+    //Only once one of them lost
+    pub fn restart(&mut self)
+    {
+        if self.decided
+        {
+            self.start();
+        }
+    }
+
+    pub fn key(&mut self, key : VirtualKeyCode, pressed : bool)
+    {
+        if key == VirtualKeyCode::Return
+        {
+            if pressed { self.restart(); }
+            return
+        }
+        for player in 0..2
+        {
+            let keys = &KEYS[player];
+            let (grid, memory) = (&mut self.grids[player], &mut self.inputs[player]);
+            if key == keys[0]
+            {
+                if pressed && !memory.rotate { grid.rotate_pair(); }
+                memory.rotate = pressed;
+            }
+            else if key == keys[1]
+            {
+                if pressed && !memory.left { grid.move_pair(-1); }
+                memory.left = pressed;
+            }
+            else if key == keys[2]
+            {
+                if pressed && !memory.right { grid.move_pair(1); }
+                memory.right = pressed;
+            }
+            else if key == keys[3]
+            {
+                if pressed && !memory.drop { grid.drop_pair(); }
+                memory.drop = pressed;
+            }
+            else if key == keys[4]
+            {
+                if pressed { grid.hold_pair(); }
+            }
+        }
+    }
+}
+
+impl<'a> Drawable for Versus<'a>
+{
+    fn draw(&self, frame: &mut glium::Frame, cam: &Camera)
+    {
+        for grid in self.grids.iter()
+        {
+            grid.draw(frame, cam);
+        }
+    }
+}
+
+impl<'a> Updatable for Versus<'a>
+{
+    fn update(&mut self, delta_t : f32)
+    {
+        for grid in self.grids.iter_mut()
+        {
+            grid.update(delta_t);
+        }
+        //Garbage crosses over as drops settle
+        let sent = [self.grids[0].take_garbage_outgoing(), self.grids[1].take_garbage_outgoing()];
+        self.grids[0].add_garbage(sent[1]);
+        self.grids[1].add_garbage(sent[0]);
+
+        if self.decided
+        {
+            return
+        }
+        let results = match (self.grids[0].is_game_over(), self.grids[1].is_game_over())
+        {
+            (true, true) => [VersusResult::DRAW, VersusResult::DRAW],
+            (true, false) => [VersusResult::LOST, VersusResult::WON],
+            (false, true) => [VersusResult::WON, VersusResult::LOST],
+            (false, false) => return,
+        };
+        //The winner stops where it stands
+        for (grid, result) in self.grids.iter_mut().zip(results.iter())
+        {
+            grid.stop();
+            grid.set_versus_result(Some(*result));
+        }
+        self.decided = true;
+    }
+}